@@ -0,0 +1,236 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::dom::{arena::NodeId, Document, DomNode};
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+/// Values and named flags attached to the nodes of a document, at most one
+/// value per type and node. Node ids are stable, so annotations survive
+/// queries and mutations. Clones of a document share the values.
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    values: HashMap<TypeId, HashMap<NodeId, Value>>,
+    flags: HashMap<String, HashSet<NodeId>>,
+}
+
+impl Annotations {
+    /// Attaches `value` to `id`, returning the previous value of that type.
+    pub fn insert<T>(&mut self, id: NodeId, value: T) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        self.values
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .insert(id, Arc::new(value))
+            .and_then(|old| old.downcast().ok())
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self, id: NodeId) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())?
+            .get(&id)?
+            .downcast_ref()
+    }
+
+    pub fn contains<T: Any + Send + Sync>(&self, id: NodeId) -> bool {
+        self.get::<T>(id).is_some()
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self, id: NodeId) -> Option<Arc<T>> {
+        self.values
+            .get_mut(&TypeId::of::<T>())?
+            .remove(&id)?
+            .downcast()
+            .ok()
+    }
+
+    /// Every node with a value of type `T`, in no particular order.
+    pub fn iter<T: Any + Send + Sync>(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flatten()
+            .filter_map(|(id, value)| Some((*id, value.downcast_ref()?)))
+    }
+
+    /// Sets the flag `name` on `id`, tested in XPath with `[?name]`.
+    pub fn set_flag(&mut self, id: NodeId, name: &str) {
+        match self.flags.get_mut(name) {
+            Some(ids) => {
+                ids.insert(id);
+            }
+            None => {
+                self.flags.insert(name.to_string(), HashSet::from([id]));
+            }
+        }
+    }
+
+    pub fn clear_flag(&mut self, id: NodeId, name: &str) -> bool {
+        self.flags.get_mut(name).is_some_and(|ids| ids.remove(&id))
+    }
+
+    pub fn has_flag(&self, id: NodeId, name: &str) -> bool {
+        self.flags.get(name).is_some_and(|ids| ids.contains(&id))
+    }
+
+    /// Nodes with the flag `name`, in no particular order.
+    pub fn flagged(&self, name: &str) -> impl Iterator<Item = NodeId> + '_ {
+        self.flags.get(name).into_iter().flatten().copied()
+    }
+
+    // drops everything attached to a node that was freed
+    pub(crate) fn remove_node(&mut self, id: NodeId) {
+        for values in self.values.values_mut() {
+            values.remove(&id);
+        }
+        for ids in self.flags.values_mut() {
+            ids.remove(&id);
+        }
+    }
+
+    // copies everything attached to `from` onto `to` of another document
+    pub(crate) fn copy_node(&self, from: NodeId, into: &mut Annotations, to: NodeId) {
+        for (type_id, values) in &self.values {
+            if let Some(value) = values.get(&from) {
+                into.values
+                    .entry(*type_id)
+                    .or_default()
+                    .insert(to, value.clone());
+            }
+        }
+        for (name, ids) in &self.flags {
+            if ids.contains(&from) {
+                into.set_flag(to, name);
+            }
+        }
+    }
+}
+
+impl Document {
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
+    }
+}
+
+impl<'a> DomNode<'a> {
+    pub fn annotation<T: Any + Send + Sync>(&self) -> Option<&'a T> {
+        self.document().annotations.get(self.node_id())
+    }
+
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.document().annotations.has_flag(self.node_id(), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dom::{Document, DomBuilder, NodeId},
+        xpath::{XPathBuilder, XPathStream},
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Score(u32);
+
+    fn query(document: &Document, xpath: &str) -> Vec<NodeId> {
+        let xpath = XPathBuilder::parse(xpath).unwrap();
+        let nodes = document.query(&xpath).collect::<Vec<_>>();
+        nodes.iter().map(|node| node.node_id()).collect()
+    }
+
+    fn parse() -> (Document, Vec<NodeId>) {
+        let document = DomBuilder::parse(
+            r#"<ul><li class="a">1</li><li class="b">2</li><li class="a">3</li></ul>"#,
+        )
+        .unwrap();
+        let items = query(&document, "//li");
+        (document, items)
+    }
+
+    #[test]
+    fn flags_are_matched_by_predicates() {
+        let (mut document, items) = parse();
+        let annotations = document.annotations_mut();
+        annotations.set_flag(items[0], "seen");
+        annotations.set_flag(items[1], "seen");
+        annotations.set_flag(items[2], "hot");
+
+        assert_eq!(query(&document, "//li[?seen]"), [items[0], items[1]]);
+        assert_eq!(query(&document, "//li[?seen & @class=\"a\"]"), [items[0]]);
+        assert_eq!(
+            query(&document, "//li[?hot | @class=\"b\"]"),
+            [items[1], items[2]]
+        );
+        assert!(query(&document, "//li[?missing]").is_empty());
+
+        assert!(document.annotations_mut().clear_flag(items[0], "seen"));
+        assert!(!document.annotations_mut().clear_flag(items[0], "seen"));
+        assert_eq!(query(&document, "//li[?seen]"), [items[1]]);
+        assert!(document.node(items[1]).unwrap().has_flag("seen"));
+    }
+
+    #[test]
+    fn values_are_typed() {
+        let (mut document, items) = parse();
+        let annotations = document.annotations_mut();
+        assert!(annotations.insert(items[0], Score(1)).is_none());
+        assert_eq!(
+            annotations.insert(items[0], Score(2)).as_deref(),
+            Some(&Score(1))
+        );
+        annotations.insert(items[0], "label");
+
+        let node = document.node(items[0]).unwrap();
+        assert_eq!(node.annotation::<Score>(), Some(&Score(2)));
+        assert_eq!(node.annotation::<&str>(), Some(&"label"));
+        assert!(document
+            .node(items[1])
+            .unwrap()
+            .annotation::<Score>()
+            .is_none());
+        assert_eq!(document.annotations().iter::<Score>().count(), 1);
+    }
+
+    #[test]
+    fn annotations_follow_mutations_and_copies() {
+        let (mut document, items) = parse();
+        document.annotations_mut().insert(items[2], Score(3));
+        document.annotations_mut().set_flag(items[2], "hot");
+        document.annotations_mut().set_flag(items[1], "hot");
+
+        // moved nodes keep their annotations
+        let root = document.root().node_id();
+        document.insert_before(items[0], items[2]).unwrap();
+        assert_eq!(query(&document, "//li[?hot]"), [items[2], items[1]]);
+
+        let copy = document.clone_subtree(root).unwrap();
+        let copied = query(&copy, "//li[?hot]");
+        assert_eq!(copied.len(), 2);
+        assert_eq!(
+            copy.node(copied[0]).unwrap().annotation::<Score>(),
+            Some(&Score(3))
+        );
+
+        // removed nodes take theirs along, reused ids start clean
+        document.remove(items[2]).unwrap();
+        assert!(document.annotations().iter::<Score>().next().is_none());
+        let li = document.create_element("li");
+        document.append_child(root, li).unwrap();
+        assert_eq!(query(&document, "//li[?hot]"), [items[1]]);
+    }
+
+    #[test]
+    fn streams_reject_flags() {
+        let xpath = XPathBuilder::parse("//li[?hot]").unwrap();
+        assert!(XPathStream::new(&xpath).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+use std::fmt;
+
+use crate::{
+    dom::node::{Attribute, AttributeRef},
+    utils::{SharedPool, SharedStr},
+};
+
+/// Handle to a node of a `Document`. Only meaningful for the document it
+/// was obtained from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub(crate) fn from_index(index: usize) -> Self {
+        NodeId(u32::try_from(index).expect("more nodes than fit in a NodeId"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Element,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Links {
+    pub parent: Option<NodeId>,
+    pub first_child: Option<NodeId>,
+    pub last_child: Option<NodeId>,
+    pub prev_sibling: Option<NodeId>,
+    pub next_sibling: Option<NodeId>,
+}
+
+#[derive(Debug, Clone)]
+struct AttributeSlot {
+    name: SharedStr,
+    // range in `attr_values`, `None` for an attribute without a value
+    value: Option<(u32, u32)>,
+}
+
+/// Flat node storage. Every node property lives in its own vector indexed
+/// by `NodeId`, and the tree is described by parent, first/last child and
+/// sibling links. Freed ids are reused by the next pushed nodes.
+#[derive(Debug, Clone, Default)]
+pub struct NodeArena {
+    kinds: Vec<NodeKind>,
+    tags: Vec<SharedStr>,
+    // range of each node in `attributes`
+    attr_ranges: Vec<(usize, usize)>,
+    attributes: Vec<AttributeSlot>,
+    // values of all attributes back to back
+    attr_values: String,
+    // per node, positions within its range sorted by name for binary search
+    attr_order: Vec<u32>,
+    // slots and value bytes no longer used by any node, compacted once they
+    // make up half of the storage
+    garbage_slots: usize,
+    garbage_bytes: usize,
+    // range of each node's text in the document's `LazyBase`
+    spans: Vec<(usize, usize)>,
+    links: Vec<Links>,
+    freed: Vec<bool>,
+    free: Vec<NodeId>,
+}
+
+impl NodeArena {
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        id.index() < self.len() && !self.freed[id.index()]
+    }
+
+    pub(crate) fn push_element<'b>(
+        &mut self,
+        tag: SharedStr,
+        attributes: impl IntoIterator<Item = AttributeRef<'b>>,
+        start: usize,
+    ) -> NodeId {
+        let attr_start = self.attributes.len();
+        for attribute in attributes {
+            let slot = self.store(attribute);
+            self.attributes.push(slot);
+        }
+        let id = self.push(
+            NodeKind::Element,
+            tag,
+            (attr_start, self.attributes.len()),
+            (start, start),
+        );
+        self.sort_attributes(id);
+        id
+    }
+
+    pub(crate) fn push_text(&mut self, span: (usize, usize)) -> NodeId {
+        let attr_start = self.attributes.len();
+        self.push(
+            NodeKind::Text,
+            SharedPool::get_or_intern("#text"),
+            (attr_start, attr_start),
+            span,
+        )
+    }
+
+    fn push(
+        &mut self,
+        kind: NodeKind,
+        tag: SharedStr,
+        attr_range: (usize, usize),
+        span: (usize, usize),
+    ) -> NodeId {
+        if let Some(id) = self.free.pop() {
+            let idx = id.index();
+            self.kinds[idx] = kind;
+            self.tags[idx] = tag;
+            self.attr_ranges[idx] = attr_range;
+            self.spans[idx] = span;
+            self.links[idx] = Links::default();
+            self.freed[idx] = false;
+            return id;
+        }
+
+        let id = NodeId::from_index(self.len());
+        self.kinds.push(kind);
+        self.tags.push(tag);
+        self.attr_ranges.push(attr_range);
+        self.spans.push(span);
+        self.links.push(Links::default());
+        self.freed.push(false);
+        id
+    }
+
+    // releases a detached node, its id is handed out again by `push`
+    pub(crate) fn free(&mut self, id: NodeId) {
+        let (start, end) = self.attr_ranges[id.index()];
+        self.discard(start, end);
+        let empty = self.attributes.len();
+        self.attr_ranges[id.index()] = (empty, empty);
+        self.links[id.index()] = Links::default();
+        self.spans[id.index()] = (0, 0);
+        self.freed[id.index()] = true;
+        self.free.push(id);
+        self.compact();
+    }
+
+    fn store(&mut self, attribute: AttributeRef) -> AttributeSlot {
+        let value = attribute.value.map(|value| {
+            let start = offset(self.attr_values.len());
+            self.attr_values.push_str(value);
+            (start, offset(self.attr_values.len()))
+        });
+        AttributeSlot {
+            name: attribute.name.clone(),
+            value,
+        }
+    }
+
+    // marks the slots in `start..end` and their values as unused
+    fn discard(&mut self, start: usize, end: usize) {
+        self.garbage_slots += end - start;
+        self.garbage_bytes += self.attributes[start..end]
+            .iter()
+            .filter_map(|slot| slot.value)
+            .map(|(start, end)| (end - start) as usize)
+            .sum::<usize>();
+    }
+
+    // rewrites the attribute storage without garbage once it makes up half
+    // of it, so repeated changes take amortized constant space
+    fn compact(&mut self) {
+        if self.garbage_slots * 2 <= self.attributes.len()
+            && self.garbage_bytes * 2 <= self.attr_values.len()
+        {
+            return;
+        }
+
+        let mut attributes = Vec::with_capacity(self.attributes.len() - self.garbage_slots);
+        let mut attr_values = String::with_capacity(self.attr_values.len() - self.garbage_bytes);
+        let mut attr_order = Vec::with_capacity(attributes.capacity());
+        for range in self.attr_ranges.iter_mut() {
+            let (start, end) = *range;
+            let new_start = attributes.len();
+            for slot in &self.attributes[start..end] {
+                let value = slot.value.map(|(start, end)| {
+                    let new_start = offset(attr_values.len());
+                    attr_values.push_str(&self.attr_values[start as usize..end as usize]);
+                    (new_start, offset(attr_values.len()))
+                });
+                attributes.push(AttributeSlot {
+                    name: slot.name.clone(),
+                    value,
+                });
+            }
+            // positions are relative to the range, so the order carries over
+            attr_order.extend_from_slice(&self.attr_order[start..end]);
+            *range = (new_start, attributes.len());
+        }
+
+        self.attributes = attributes;
+        self.attr_values = attr_values;
+        self.attr_order = attr_order;
+        self.garbage_slots = 0;
+        self.garbage_bytes = 0;
+    }
+
+    // slots and value bytes in use, garbage included
+    #[cfg(test)]
+    pub(crate) fn attribute_storage(&self) -> (usize, usize) {
+        (self.attributes.len(), self.attr_values.len())
+    }
+
+    fn attribute_ref<'a>(&'a self, slot: &'a AttributeSlot) -> AttributeRef<'a> {
+        AttributeRef {
+            name: &slot.name,
+            value: slot
+                .value
+                .map(|(start, end)| &self.attr_values[start as usize..end as usize]),
+        }
+    }
+
+    pub(crate) fn append_child(&mut self, parent: NodeId, child: NodeId) {
+        let last = self.links[parent.index()].last_child;
+        match last {
+            Some(last) => self.links[last.index()].next_sibling = Some(child),
+            None => self.links[parent.index()].first_child = Some(child),
+        }
+
+        let links = &mut self.links[child.index()];
+        links.parent = Some(parent);
+        links.prev_sibling = last;
+        links.next_sibling = None;
+        self.links[parent.index()].last_child = Some(child);
+    }
+
+    pub(crate) fn insert_before(&mut self, reference: NodeId, child: NodeId) {
+        let parent = self.links[reference.index()].parent;
+        let prev = self.links[reference.index()].prev_sibling;
+        match prev {
+            Some(prev) => self.links[prev.index()].next_sibling = Some(child),
+            None => {
+                if let Some(parent) = parent {
+                    self.links[parent.index()].first_child = Some(child);
+                }
+            }
+        }
+
+        let links = &mut self.links[child.index()];
+        links.parent = parent;
+        links.prev_sibling = prev;
+        links.next_sibling = Some(reference);
+        self.links[reference.index()].prev_sibling = Some(child);
+    }
+
+    // unlinks the node from its parent and siblings, keeping its subtree
+    pub(crate) fn detach(&mut self, id: NodeId) {
+        let Links {
+            parent,
+            prev_sibling,
+            next_sibling,
+            ..
+        } = self.links[id.index()];
+
+        match prev_sibling {
+            Some(prev) => self.links[prev.index()].next_sibling = next_sibling,
+            None => {
+                if let Some(parent) = parent {
+                    self.links[parent.index()].first_child = next_sibling;
+                }
+            }
+        }
+        match next_sibling {
+            Some(next) => self.links[next.index()].prev_sibling = prev_sibling,
+            None => {
+                if let Some(parent) = parent {
+                    self.links[parent.index()].last_child = prev_sibling;
+                }
+            }
+        }
+
+        let links = &mut self.links[id.index()];
+        links.parent = None;
+        links.prev_sibling = None;
+        links.next_sibling = None;
+    }
+
+    pub(crate) fn set_tag(&mut self, id: NodeId, tag: SharedStr) {
+        self.tags[id.index()] = tag;
+    }
+
+    pub(crate) fn set_attribute(&mut self, id: NodeId, attribute: Attribute) {
+        let (start, end) = self.attr_ranges[id.index()];
+        let attribute = self.store((&attribute).into());
+        if let Some(existing) = self.attributes[start..end]
+            .iter_mut()
+            .find(|a| a.name == attribute.name)
+        {
+            if let Some((start, end)) = existing.value {
+                self.garbage_bytes += (end - start) as usize;
+            }
+            existing.value = attribute.value;
+            self.compact();
+            return;
+        }
+
+        // the range has to stay contiguous, move it to the end unless it's there already
+        if end != self.attributes.len() {
+            self.attributes.extend_from_within(start..end);
+            self.garbage_slots += end - start;
+        }
+        self.attributes.push(attribute);
+        let len = self.attributes.len();
+        self.attr_ranges[id.index()] = (len - (end - start) - 1, len);
+        self.sort_attributes(id);
+        self.compact();
+    }
+
+    pub(crate) fn remove_attribute(&mut self, id: NodeId, name: &str) -> bool {
+        let (start, end) = self.attr_ranges[id.index()];
+        match self.attributes[start..end]
+            .iter()
+            .position(|a| a.name.as_ref() == name)
+        {
+            Some(idx) => {
+                self.attributes[start + idx..end].rotate_left(1);
+                self.discard(end - 1, end);
+                self.attr_ranges[id.index()] = (start, end - 1);
+                self.sort_attributes(id);
+                self.compact();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn sort_attributes(&mut self, id: NodeId) {
+        let (start, end) = self.attr_ranges[id.index()];
+        self.attr_order.resize(self.attributes.len(), 0);
+
+        let attributes = &self.attributes[start..end];
+        let order = &mut self.attr_order[start..end];
+        for (idx, pos) in order.iter_mut().enumerate() {
+            *pos = offset(idx);
+        }
+        // stable, so the first of duplicated names is found
+        order.sort_by(|a, b| {
+            attributes[*a as usize]
+                .name
+                .cmp(&attributes[*b as usize].name)
+        });
+    }
+
+    pub(crate) fn set_span_end(&mut self, id: NodeId, end: usize) {
+        self.spans[id.index()].1 = end;
+    }
+
+    pub(crate) fn set_spans(&mut self, spans: Vec<(usize, usize)>) {
+        self.spans = spans;
+    }
+
+    pub fn kind(&self, id: NodeId) -> NodeKind {
+        self.kinds[id.index()]
+    }
+
+    pub fn tag(&self, id: NodeId) -> &SharedStr {
+        &self.tags[id.index()]
+    }
+
+    pub fn attributes(&self, id: NodeId) -> Attributes<'_> {
+        let (start, end) = self.attr_ranges[id.index()];
+        Attributes {
+            arena: self,
+            slots: self.attributes[start..end].iter(),
+        }
+    }
+
+    /// Finds an attribute by name with a binary search.
+    pub fn attribute(&self, id: NodeId, name: &str) -> Option<AttributeRef<'_>> {
+        let (start, end) = self.attr_ranges[id.index()];
+        let attributes = &self.attributes[start..end];
+        let order = &self.attr_order[start..end];
+
+        let idx = order.partition_point(|pos| attributes[*pos as usize].name.as_ref() < name);
+        order
+            .get(idx)
+            .map(|pos| &attributes[*pos as usize])
+            .filter(|attribute| attribute.name.as_ref() == name)
+            .map(|slot| self.attribute_ref(slot))
+    }
+
+    // only valid for the text buffer the spans were last set for, readers go
+    // through `Document::span`
+    pub(crate) fn span(&self, id: NodeId) -> (usize, usize) {
+        self.spans[id.index()]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].last_child
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].next_sibling
+    }
+
+    pub fn prev_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].prev_sibling
+    }
+}
+
+// attribute storage is addressed with u32 to keep slots small
+fn offset(value: usize) -> u32 {
+    u32::try_from(value).expect("attribute storage exceeds u32::MAX")
+}
+
+/// Attributes of a node in source order.
+#[derive(Clone)]
+pub struct Attributes<'a> {
+    arena: &'a NodeArena,
+    slots: std::slice::Iter<'a, AttributeSlot>,
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = AttributeRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.next()?;
+        Some(self.arena.attribute_ref(slot))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Attributes<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.next_back()?;
+        Some(self.arena.attribute_ref(slot))
+    }
+}
+
+impl ExactSizeIterator for Attributes<'_> {}
+
+impl fmt::Debug for Attributes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::DomBuilder;
+
+    #[test]
+    fn links_are_consistent() {
+        let document = DomBuilder::parse("<div>a<p>b</p><br/><i>c</i></div>").unwrap();
+        let nodes = document.nodes();
+        let root = document.root().node_id();
+        let children = std::iter::successors(nodes.first_child(root), |id| nodes.next_sibling(*id))
+            .collect::<Vec<_>>();
+        let tags = children
+            .iter()
+            .map(|id| nodes.tag(*id).as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(tags, ["#text", "p", "br", "i"]);
+
+        assert_eq!(nodes.parent(root), None);
+        assert!(children.iter().all(|id| nodes.parent(*id) == Some(root)));
+        assert_eq!(nodes.last_child(root), children.last().copied());
+        assert_eq!(nodes.prev_sibling(children[0]), None);
+        for pair in children.windows(2) {
+            assert_eq!(nodes.prev_sibling(pair[1]), Some(pair[0]));
+        }
+    }
+}
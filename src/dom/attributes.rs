@@ -0,0 +1,251 @@
+use std::str::SplitAsciiWhitespace;
+
+use crate::dom::{
+    arena::Attributes,
+    node::{Attribute, AttributeRef, AttributeValue},
+    DomNode,
+};
+
+impl AttributeValue {
+    /// The value, empty for attributes without one.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AttributeValue::Exists => "",
+            AttributeValue::Literal(value) => value,
+        }
+    }
+}
+
+impl<'a> AttributeRef<'a> {
+    /// The value, empty for attributes without one.
+    pub fn value_str(&self) -> &'a str {
+        self.value.unwrap_or_default()
+    }
+
+    pub fn to_attribute(&self) -> Attribute {
+        Attribute {
+            name: self.name.clone(),
+            value: match self.value {
+                Some(value) => AttributeValue::Literal(value.to_string()),
+                None => AttributeValue::Exists,
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a Attribute> for AttributeRef<'a> {
+    fn from(attribute: &'a Attribute) -> Self {
+        AttributeRef {
+            name: &attribute.name,
+            value: match &attribute.value {
+                AttributeValue::Exists => None,
+                AttributeValue::Literal(value) => Some(value),
+            },
+        }
+    }
+}
+
+impl<'a> DomNode<'a> {
+    pub fn attribute(&self, name: &str) -> Option<AttributeRef<'a>> {
+        self.document().nodes.attribute(self.node_id(), name)
+    }
+
+    /// Value of the attribute, empty if it's present without one.
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attribute(name).map(|attribute| attribute.value_str())
+    }
+
+    pub fn has_attr(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
+
+    /// The `id` attribute.
+    pub fn id(&self) -> Option<&'a str> {
+        self.attr("id")
+    }
+
+    /// Whitespace separated names of the `class` attribute.
+    pub fn classes(&self) -> SplitAsciiWhitespace<'a> {
+        self.attr("class")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes().any(|name| name == class)
+    }
+
+    /// The `data-*` attributes, keyed like `HTMLElement.dataset`.
+    pub fn dataset(&self) -> Dataset<'a> {
+        Dataset { node: *self }
+    }
+}
+
+/// The `data-*` attributes of an element, with camelCase keys: `data-foo-bar`
+/// is found as `fooBar`.
+#[derive(Debug, Clone, Copy)]
+pub struct Dataset<'a> {
+    node: DomNode<'a>,
+}
+
+impl<'a> Dataset<'a> {
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        // `foo-bar` would map back to the name of `fooBar`
+        if key
+            .as_bytes()
+            .windows(2)
+            .any(|pair| pair[0] == b'-' && pair[1].is_ascii_lowercase())
+        {
+            return None;
+        }
+
+        let mut name = String::with_capacity(key.len() + 8);
+        name.push_str("data-");
+        for c in key.chars() {
+            if c.is_ascii_uppercase() {
+                name.push('-');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        self.node.attr(&name)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> DatasetIter<'a> {
+        DatasetIter {
+            attributes: self.node.attributes(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for Dataset<'a> {
+    type Item = (String, &'a str);
+    type IntoIter = DatasetIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatasetIter<'a> {
+    attributes: Attributes<'a>,
+}
+
+impl<'a> Iterator for DatasetIter<'a> {
+    type Item = (String, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.attributes.by_ref().find_map(|attribute| {
+            let name = attribute.name.strip_prefix("data-")?;
+
+            let mut key = String::with_capacity(name.len());
+            let mut chars = name.chars().peekable();
+            while let Some(c) = chars.next() {
+                match chars.peek() {
+                    Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                        key.push(next.to_ascii_uppercase());
+                        chars.next();
+                    }
+                    _ => key.push(c),
+                }
+            }
+            Some((key, attribute.value_str()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::{Document, DomBuilder};
+
+    fn parse(input: &str) -> Document {
+        DomBuilder::parse(input).unwrap()
+    }
+
+    #[test]
+    fn attribute_values() {
+        let document = parse(r#"<a id="k" href="/x" hidden title="">a</a>"#);
+        let a = document.root();
+        assert_eq!(a.id(), Some("k"));
+        assert_eq!(a.attr("href"), Some("/x"));
+        assert_eq!(a.attr("hidden"), Some(""));
+        assert_eq!(a.attr("title"), Some(""));
+        assert_eq!(a.attr("rel"), None);
+        assert!(a.has_attr("hidden"));
+        assert!(!a.has_attr("rel"));
+        assert_eq!(a.attribute("hidden").unwrap().value, None);
+        assert_eq!(a.attribute("title").unwrap().value, Some(""));
+    }
+
+    #[test]
+    fn classes_skip_runs_of_whitespace() {
+        let document = parse("<p class=\"  one   two\tthree \">a</p>");
+        let p = document.root();
+        assert_eq!(p.classes().collect::<Vec<_>>(), ["one", "two", "three"]);
+        assert!(p.has_class("two"));
+        assert!(!p.has_class(""));
+        assert!(!p.has_class("on"));
+
+        let document = parse("<p class=\"   \">a</p>");
+        assert_eq!(document.root().classes().count(), 0);
+    }
+
+    #[test]
+    fn dataset_keys_are_camel_case() {
+        let document =
+            parse(r#"<p data-foo-bar="1" data-x="" data-a-b-c="2" title="t" data-id="3">a</p>"#);
+        let dataset = document.root().dataset();
+        assert_eq!(dataset.get("fooBar"), Some("1"));
+        assert_eq!(dataset.get("x"), Some(""));
+        assert_eq!(dataset.get("aBC"), Some("2"));
+        assert_eq!(dataset.get("id"), Some("3"));
+        assert!(dataset.contains("x"));
+        assert!(!dataset.contains("title"));
+        // only the camelCase key finds `data-foo-bar`
+        assert_eq!(dataset.get("foo-bar"), None);
+        assert_eq!(dataset.get("foobar"), None);
+
+        let keys = dataset.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                ("fooBar".to_string(), "1"),
+                ("x".to_string(), ""),
+                ("aBC".to_string(), "2"),
+                ("id".to_string(), "3"),
+            ]
+        );
+        for (key, value) in dataset.iter() {
+            assert_eq!(dataset.get(&key), Some(value));
+        }
+    }
+
+    #[test]
+    fn exists_and_empty_values_stay_distinct() {
+        let document = parse(r#"<div><a hidden>a</a><a hidden="">b</a></div>"#);
+        let mut links = document.root().children();
+        let (exists, empty) = (links.next().unwrap(), links.next().unwrap());
+        assert_eq!(exists.attribute("hidden").unwrap().value, None);
+        assert_eq!(empty.attribute("hidden").unwrap().value, Some(""));
+        assert!(document.html().contains(r#"<a hidden>a</a>"#));
+        assert!(document.html().contains(r#"<a hidden="">b</a>"#));
+    }
+
+    #[test]
+    fn values_are_stored_back_to_back() {
+        let document = parse(r#"<p title='say "hi"' lang="en" data-x='' hidden>a</p>"#);
+        let p = document.root();
+        assert_eq!(p.attr("title"), Some(r#"say "hi""#));
+        assert_eq!(p.attr("lang"), Some("en"));
+        assert_eq!(p.attribute("data-x").unwrap().value, Some(""));
+        assert_eq!(p.attribute("hidden").unwrap().value, None);
+        assert_eq!(
+            document.nodes().attribute_storage(),
+            (4, r#"say "hi"en"#.len())
+        );
+    }
+}
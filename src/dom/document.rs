@@ -1,19 +1,123 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
-    dom::DomNode,
-    utils::LazyBase,
+    dom::{
+        annotations::Annotations,
+        arena::{NodeArena, NodeId},
+        index::DocumentIndex,
+        mutation::{Layout, OwnedText},
+        DomNode,
+    },
+    utils::{Interner, LazyBase, LazyStr},
     xpath::{filter::XPathFilter, XPath},
 };
 
-#[derive(Debug)]
+// clones share the immutable text buffer until either is mutated
+#[derive(Clone)]
 pub struct Document {
-    pub lazy_base: Arc<LazyBase>,
-    pub root: DomNode,
+    // text buffer the spans in `nodes` point into, see `lazy_base()`
+    pub(crate) lazy_base: Arc<LazyBase>,
+    pub(crate) nodes: NodeArena,
+    pub(crate) root: NodeId,
+    pub(crate) index: Option<DocumentIndex>,
+    // interns the names of created elements and attributes
+    pub(crate) interner: Arc<dyn Interner>,
+    pub(crate) annotations: Annotations,
+    // text of the text nodes missing from `lazy_base`: new, changed or
+    // detached ones
+    pub(crate) texts: HashMap<NodeId, OwnedText>,
+    // set by mutations that changed the text of the tree
+    pub(crate) dirty: bool,
+    // text buffer of the mutated tree, built on the first read
+    pub(crate) layout: OnceLock<Layout>,
+}
+
+/// A document whose parse may have been stopped early.
+#[derive(Debug)]
+pub struct PartialDocument {
+    pub document: Document,
+    pub truncated: bool,
 }
 
 impl Document {
+    pub(crate) fn new(
+        lazy_base: Arc<LazyBase>,
+        nodes: NodeArena,
+        root: NodeId,
+        index: Option<DocumentIndex>,
+        interner: Arc<dyn Interner>,
+    ) -> Self {
+        Document {
+            lazy_base,
+            nodes,
+            root,
+            index,
+            interner,
+            annotations: Annotations::default(),
+            texts: HashMap::new(),
+            dirty: false,
+            layout: OnceLock::new(),
+        }
+    }
+
+    pub fn root(&self) -> DomNode<'_> {
+        DomNode::new(self, self.root)
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<DomNode<'_>> {
+        match self.nodes.contains(id) {
+            true => Some(DomNode::new(self, id)),
+            false => None,
+        }
+    }
+
+    pub fn nodes(&self) -> &NodeArena {
+        &self.nodes
+    }
+
+    /// Text of the document that searches and text predicates run on, the
+    /// text of neighbouring nodes separated by a space. Rebuilt on the first
+    /// call after a mutation.
+    pub fn lazy_base(&self) -> &Arc<LazyBase> {
+        match self.layout() {
+            Some(layout) => &layout.base,
+            None => &self.lazy_base,
+        }
+    }
+
+    // range of the node's text in `lazy_base()`, empty for detached nodes
+    pub(crate) fn span(&self, id: NodeId) -> (usize, usize) {
+        match self.layout() {
+            Some(layout) => layout.spans[id.index()],
+            None => self.nodes.span(id),
+        }
+    }
+
     pub fn query<'a>(&'a self, xpath: &'a XPath) -> XPathFilter<'a> {
-        XPathFilter::new_with_node(xpath, &self.root)
+        XPathFilter::new_with_node(xpath, self.root())
     }
 }
+
+impl fmt::Debug for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Document")
+            .field("root", &self.root())
+            .finish()
+    }
+}
+
+// Parsed documents are immutable apart from `&mut self` mutations, so they
+// can be cached and queried from several threads. Fails to compile otherwise.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Document>();
+    assert_send_sync::<PartialDocument>();
+    assert_send_sync::<DomNode<'static>>();
+    assert_send_sync::<LazyBase>();
+    assert_send_sync::<LazyStr>();
+};
@@ -0,0 +1,193 @@
+use std::{borrow::Borrow, cmp::Ordering, collections::HashMap, hash::Hash};
+
+use crate::{
+    dom::{
+        arena::{Attributes, NodeArena, NodeId, NodeKind},
+        Document, DomNode,
+    },
+    utils::SharedStr,
+    xpath::filter::DescendantIterator,
+};
+
+/// Lookup tables from id, tag and class to elements, each list in document
+/// order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocumentIndex {
+    ids: HashMap<String, Vec<NodeId>>,
+    tags: HashMap<SharedStr, Vec<NodeId>>,
+    classes: HashMap<String, Vec<NodeId>>,
+}
+
+impl DocumentIndex {
+    // elements have to be inserted in document order
+    pub fn insert(&mut self, id: NodeId, tag: &SharedStr, attributes: Attributes) {
+        self.tags.entry(tag.clone()).or_default().push(id);
+        if let Some(value) = id_key(attributes.clone()) {
+            self.ids.entry(value.to_string()).or_default().push(id);
+        }
+        for class in class_keys(attributes) {
+            self.classes.entry(class.to_string()).or_default().push(id);
+        }
+    }
+
+    /// Indexes an element anywhere in the tree, `order` compares nodes in
+    /// document order.
+    pub fn insert_ordered<F>(
+        &mut self,
+        id: NodeId,
+        tag: &SharedStr,
+        attributes: Attributes,
+        order: F,
+    ) where
+        F: Fn(NodeId, NodeId) -> Ordering,
+    {
+        insert_sorted(self.tags.entry(tag.clone()).or_default(), id, &order);
+        if let Some(value) = id_key(attributes.clone()) {
+            insert_sorted(self.ids.entry(value.to_string()).or_default(), id, &order);
+        }
+        for class in class_keys(attributes) {
+            insert_sorted(
+                self.classes.entry(class.to_string()).or_default(),
+                id,
+                &order,
+            );
+        }
+    }
+
+    /// Drops an element, with the tag and attributes it was indexed with.
+    pub fn remove(&mut self, id: NodeId, tag: &SharedStr, attributes: Attributes) {
+        remove_from(&mut self.tags, tag.as_ref(), id);
+        if let Some(value) = id_key(attributes.clone()) {
+            remove_from(&mut self.ids, value, id);
+        }
+        for class in class_keys(attributes) {
+            remove_from(&mut self.classes, class, id);
+        }
+    }
+
+    /// Indexes the elements below `root`, `root` included.
+    pub fn build(nodes: &NodeArena, root: NodeId) -> Self {
+        let mut index = DocumentIndex::default();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if nodes.kind(id) != NodeKind::Element {
+                continue;
+            }
+            index.insert(id, nodes.tag(id), nodes.attributes(id));
+
+            let mut child = nodes.last_child(id);
+            while let Some(id) = child {
+                stack.push(id);
+                child = nodes.prev_sibling(id);
+            }
+        }
+        index
+    }
+
+    pub fn by_id(&self, id: &str) -> &[NodeId] {
+        self.ids.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn by_tag(&self, tag: &str) -> &[NodeId] {
+        self.tags.get(tag).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn by_class(&self, class: &str) -> &[NodeId] {
+        self.classes.get(class).map_or(&[], Vec::as_slice)
+    }
+}
+
+// like lookups on the node, the first of duplicated names wins
+fn id_key(mut attributes: Attributes<'_>) -> Option<&str> {
+    attributes
+        .find(|a| a.name.as_ref() == "id")
+        .map(|a| a.value_str())
+}
+
+fn class_keys(mut attributes: Attributes<'_>) -> Vec<&str> {
+    let mut classes = Vec::new();
+    if let Some(value) = attributes.find(|a| a.name.as_ref() == "class") {
+        for class in value.value_str().split_ascii_whitespace() {
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+    }
+    classes
+}
+
+fn insert_sorted<F>(ids: &mut Vec<NodeId>, id: NodeId, order: F)
+where
+    F: Fn(NodeId, NodeId) -> Ordering,
+{
+    let pos = ids.partition_point(|other| order(*other, id) == Ordering::Less);
+    ids.insert(pos, id);
+}
+
+fn remove_from<K>(map: &mut HashMap<K, Vec<NodeId>>, key: &str, id: NodeId)
+where
+    K: Borrow<str> + Hash + Eq,
+{
+    if let Some(ids) = map.get_mut(key) {
+        if let Some(pos) = ids.iter().position(|other| *other == id) {
+            ids.remove(pos);
+        }
+        if ids.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+// Without an index the lookups walk the tree.
+impl Document {
+    /// Builds the id, tag and class indexes, kept up to date by mutations
+    /// from then on.
+    pub fn build_index(&mut self) {
+        self.index = Some(DocumentIndex::build(&self.nodes, self.root));
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.index.is_some()
+    }
+
+    pub(crate) fn index(&self) -> Option<&DocumentIndex> {
+        self.index.as_ref()
+    }
+
+    /// The first element with the id `id`.
+    pub fn get_element_by_id(&self, id: &str) -> Option<DomNode<'_>> {
+        match &self.index {
+            Some(index) => index.by_id(id).first().map(|id| DomNode::new(self, *id)),
+            None => self.elements().find(|node| node.id() == Some(id)),
+        }
+    }
+
+    pub fn get_elements_by_tag(&self, tag: &str) -> Vec<DomNode<'_>> {
+        match &self.index {
+            Some(index) => self.views(index.by_tag(tag)),
+            None => self
+                .elements()
+                .filter(|node| node.tag().as_ref() == tag)
+                .collect(),
+        }
+    }
+
+    pub fn get_elements_by_class(&self, class: &str) -> Vec<DomNode<'_>> {
+        match &self.index {
+            Some(index) => self.views(index.by_class(class)),
+            None => self
+                .elements()
+                .filter(|node| node.has_class(class))
+                .collect(),
+        }
+    }
+
+    fn views(&self, ids: &[NodeId]) -> Vec<DomNode<'_>> {
+        ids.iter().map(|id| DomNode::new(self, *id)).collect()
+    }
+
+    // elements in document order
+    fn elements(&self) -> DescendantIterator<'_> {
+        self.root().descendants()
+    }
+}
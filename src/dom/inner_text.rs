@@ -0,0 +1,308 @@
+use std::{collections::HashSet, sync::OnceLock};
+
+use crate::dom::{serializer::collapse_whitespace, DomNode};
+
+// Rendering follows the innerText algorithm: the tree is turned into runs of
+// text and required line break counts, then consecutive counts are merged.
+enum Item {
+    Text(String),
+    Breaks(usize),
+}
+
+enum Step<'a> {
+    Enter(DomNode<'a>),
+    Leave(DomNode<'a>),
+}
+
+fn block_tags() -> &'static HashSet<&'static str> {
+    static BLOCK_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+    BLOCK_TAGS.get_or_init(|| {
+        HashSet::from([
+            "address",
+            "article",
+            "aside",
+            "blockquote",
+            "body",
+            "caption",
+            "center",
+            "dd",
+            "details",
+            "dialog",
+            "dir",
+            "div",
+            "dl",
+            "dt",
+            "fieldset",
+            "figcaption",
+            "figure",
+            "footer",
+            "form",
+            "h1",
+            "h2",
+            "h3",
+            "h4",
+            "h5",
+            "h6",
+            "header",
+            "hgroup",
+            "hr",
+            "html",
+            "legend",
+            "li",
+            "listing",
+            "main",
+            "menu",
+            "nav",
+            "ol",
+            "pre",
+            "section",
+            "summary",
+            "table",
+            "ul",
+            "xmp",
+        ])
+    })
+}
+
+// elements which aren't rendered by default
+fn hidden_tags() -> &'static HashSet<&'static str> {
+    static HIDDEN_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+    HIDDEN_TAGS.get_or_init(|| {
+        HashSet::from([
+            "area", "base", "datalist", "head", "link", "meta", "noembed", "noframes", "noscript",
+            "param", "rp", "script", "style", "template", "title",
+        ])
+    })
+}
+
+fn preserve_tags() -> &'static HashSet<&'static str> {
+    static PRESERVE_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+    PRESERVE_TAGS.get_or_init(|| HashSet::from(["listing", "plaintext", "pre", "textarea", "xmp"]))
+}
+
+fn is_hidden(node: DomNode) -> bool {
+    if hidden_tags().contains(node.tag().as_ref()) {
+        return true;
+    }
+
+    if node.has_attr("hidden") {
+        return true;
+    }
+
+    node.attr("style").is_some_and(|style| {
+        style
+            .split(';')
+            .filter_map(|decl| decl.split_once(':'))
+            .any(|(prop, value)| {
+                prop.trim().eq_ignore_ascii_case("display")
+                    && value.trim().eq_ignore_ascii_case("none")
+            })
+    })
+}
+
+// a row is followed by another one in its own or the next row group
+fn has_next_row(node: DomNode) -> bool {
+    node.next_sibling().is_some()
+        || node.parent().is_some_and(|parent| {
+            matches!(parent.tag().as_ref(), "thead" | "tbody" | "tfoot")
+                && parent.next_sibling().is_some()
+        })
+}
+
+#[derive(Default)]
+struct Renderer {
+    items: Vec<Item>,
+    // number of open whitespace preserving elements
+    preserve: usize,
+}
+
+impl Renderer {
+    fn text(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        match self.items.last_mut() {
+            // the parser trims text, so separate runs had whitespace between them
+            Some(Item::Text(last)) => {
+                if !last.ends_with(char::is_whitespace) && !text.starts_with(char::is_whitespace) {
+                    last.push(' ');
+                }
+                last.push_str(&text);
+            }
+            _ => self.items.push(Item::Text(text)),
+        }
+    }
+
+    fn breaks(&mut self, count: usize) {
+        self.items.push(Item::Breaks(count));
+    }
+
+    fn render(self) -> String {
+        let mut out = String::new();
+        let mut pending = 0;
+        for item in self.items {
+            match item {
+                Item::Breaks(count) => pending = pending.max(count),
+                Item::Text(text) => {
+                    // spaces at the start and end of lines are collapsed away
+                    let text = match pending > 0 || out.is_empty() {
+                        true => text.trim_start_matches(' '),
+                        false => &text,
+                    };
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if pending > 0 && !out.is_empty() {
+                        let trimmed = out.trim_end_matches(' ').len();
+                        out.truncate(trimmed);
+                        out.push_str(&"\n".repeat(pending));
+                    }
+                    pending = 0;
+                    out.push_str(text);
+                }
+            }
+        }
+
+        let trimmed = out.trim_end_matches(' ').len();
+        out.truncate(trimmed);
+        out
+    }
+}
+
+impl DomNode<'_> {
+    /// Text as a browser would render it: block elements on their own lines,
+    /// table cells separated by tabs, `<br>` as a newline, hidden elements
+    /// skipped and whitespace collapsed outside of `pre` and the like.
+    pub fn inner_text(&self) -> String {
+        let mut renderer = Renderer {
+            preserve: self
+                .ancestors()
+                .filter(|node| preserve_tags().contains(node.tag().as_ref()))
+                .count(),
+            ..Default::default()
+        };
+
+        let mut stack = vec![Step::Enter(*self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) if node.is_text() => {
+                    let text = node.text();
+                    match renderer.preserve {
+                        0 => renderer.text(collapse_whitespace(text)),
+                        _ => renderer.text(text.to_string()),
+                    }
+                }
+                Step::Enter(node) => {
+                    // the node itself is always rendered, like in browsers
+                    if node.node_id() != self.node_id() && is_hidden(node) {
+                        continue;
+                    }
+
+                    let tag = node.tag().as_ref();
+                    if tag == "br" {
+                        renderer.text("\n".to_string());
+                    }
+                    match tag {
+                        "p" => renderer.breaks(2),
+                        _ if block_tags().contains(tag) => renderer.breaks(1),
+                        _ => {}
+                    }
+                    if preserve_tags().contains(tag) {
+                        renderer.preserve += 1;
+                    }
+
+                    stack.push(Step::Leave(node));
+                    let mut child = node.last_child_node();
+                    while let Some(node) = child {
+                        stack.push(Step::Enter(node));
+                        child = node.prev_node();
+                    }
+                }
+                Step::Leave(node) => {
+                    let tag = node.tag().as_ref();
+                    if preserve_tags().contains(tag) {
+                        renderer.preserve -= 1;
+                    }
+                    match tag {
+                        "td" | "th" if node.next_sibling().is_some() => {
+                            renderer.text("\t".to_string())
+                        }
+                        "tr" if has_next_row(node) => renderer.breaks(1),
+                        "p" => renderer.breaks(2),
+                        _ if block_tags().contains(tag) => renderer.breaks(1),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        renderer.render()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::DomBuilder;
+
+    fn inner_text(input: &str) -> String {
+        DomBuilder::parse(input).unwrap().root().inner_text()
+    }
+
+    #[test]
+    fn tables_use_tabs_and_newlines() {
+        assert_eq!(
+            inner_text("<table><tr><td>a</td><td>b</td></tr><tr><th>c</th><td>d</td></tr></table>"),
+            "a\tb\nc\td"
+        );
+        // rows continue across row groups
+        assert_eq!(
+            inner_text(
+                "<table><thead><tr><th>h</th></tr></thead>\
+                 <tbody><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr></tbody></table>"
+            ),
+            "h\na\tb\nc"
+        );
+    }
+
+    #[test]
+    fn br_is_a_newline() {
+        assert_eq!(inner_text("<div>a<br>b<br><br>c</div>"), "a\nb\n\nc");
+    }
+
+    #[test]
+    fn hidden_elements_are_skipped() {
+        assert_eq!(
+            inner_text(
+                "<div>a <span hidden>x</span><span style=\"color: red; display : NONE\">y</span>\
+                 <script>z</script><head><title>t</title></head> b</div>"
+            ),
+            "a b"
+        );
+        // unless it's the node itself
+        assert_eq!(inner_text("<div hidden>a</div>"), "a");
+    }
+
+    #[test]
+    fn pre_keeps_whitespace() {
+        assert_eq!(
+            inner_text("<div><pre>a   b\n  c</pre><p>x   y\n z</p></div>"),
+            "a   b\n  c\n\nx y z"
+        );
+        let document = DomBuilder::parse("<pre><b>a   b</b></pre>").unwrap();
+        let b = document.root().children().next().unwrap();
+        assert_eq!(b.inner_text(), "a   b");
+    }
+
+    #[test]
+    fn paragraphs_are_followed_by_a_blank_line() {
+        assert_eq!(
+            inner_text("<div><p>a</p><p>b</p>c<div>d</div></div>"),
+            "a\n\nb\n\nc\nd"
+        );
+        assert_eq!(inner_text("<div><h1>t</h1>x <b>y</b></div>"), "t\nx y");
+    }
+}
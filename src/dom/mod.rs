@@ -1,8 +1,24 @@
+pub mod annotations;
+pub mod arena;
+pub mod attributes;
 pub mod document;
 pub mod error;
+pub mod index;
+pub mod inner_text;
+pub mod mutation;
+pub mod navigation;
 pub mod node;
+pub mod order;
 pub mod parser;
+pub mod search;
+pub mod serializer;
+pub mod walker;
 
-pub use document::Document;
+pub use annotations::Annotations;
+pub use arena::{Attributes, NodeArena, NodeId, NodeKind};
+pub use document::{Document, PartialDocument};
 pub use node::DomNode;
-pub use parser::DomBuilder;
+pub use parser::{tokens, DomBuilder};
+pub use search::{Snippet, TextMatch};
+pub use serializer::{Format, HtmlSerializer};
+pub use walker::{TreeWalker, Visitor, WalkEvent, WalkFilter};
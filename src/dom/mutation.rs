@@ -0,0 +1,782 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    dom::{
+        annotations::Annotations,
+        arena::{NodeArena, NodeId, NodeKind},
+        error::{DomError, DomResult},
+        index::DocumentIndex,
+        node::{Attribute, AttributeValue},
+        Document, DomNode,
+    },
+    utils::{LazyBase, LazyBaseBuilder},
+};
+
+/// Text buffer of a mutated tree with the span of every node in it.
+#[derive(Debug, Clone)]
+pub(crate) struct Layout {
+    pub base: Arc<LazyBase>,
+    pub spans: Vec<(usize, usize)>,
+    // text nodes whose text moved from `Document::texts` into `base`
+    consumed: Vec<NodeId>,
+}
+
+/// Text of a text node kept outside the document buffer.
+#[derive(Debug, Clone)]
+pub(crate) struct OwnedText {
+    pub text: String,
+    // buffer of its own for detached text nodes, built on the first read
+    base: OnceLock<Arc<LazyBase>>,
+}
+
+impl OwnedText {
+    fn new(text: &str) -> Self {
+        OwnedText {
+            text: text.to_string(),
+            base: OnceLock::new(),
+        }
+    }
+
+    pub fn base(&self) -> &Arc<LazyBase> {
+        self.base
+            .get_or_init(|| Arc::new(LazyBase::init(self.text.clone())))
+    }
+}
+
+// Structural changes only mark the text as stale, the text buffer and the
+// spans are rebuilt once on the next read. Detached subtrees are left out of
+// the buffer, their text nodes keep their text in `texts` until inserted
+// again. The index is updated per element that enters, leaves or changes.
+impl Document {
+    /// Creates a detached element, insert it with `append_child` and co.
+    pub fn create_element(&mut self, tag: &str) -> NodeId {
+        self.settle();
+        let tag = self.interner.intern(tag);
+        self.nodes.push_element(tag, [], 0)
+    }
+
+    /// Creates a detached text node.
+    pub fn create_text(&mut self, text: &str) -> NodeId {
+        self.settle();
+        let id = self.nodes.push_text((0, 0));
+        self.texts.insert(id, OwnedText::new(text));
+        id
+    }
+
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> DomResult<()> {
+        self.check_insert(parent, child)?;
+        self.settle();
+        self.unlink(child);
+        self.nodes.append_child(parent, child);
+        self.link(child);
+        Ok(())
+    }
+
+    /// Inserts `child` as the previous sibling of `reference`.
+    pub fn insert_before(&mut self, reference: NodeId, child: NodeId) -> DomResult<()> {
+        let parent = self.parent_of(reference)?;
+        self.check_insert(parent, child)?;
+        if child != reference {
+            self.settle();
+            self.unlink(child);
+            self.nodes.insert_before(reference, child);
+            self.link(child);
+        }
+        Ok(())
+    }
+
+    /// Inserts `child` as the next sibling of `reference`.
+    pub fn insert_after(&mut self, reference: NodeId, child: NodeId) -> DomResult<()> {
+        let parent = self.parent_of(reference)?;
+        match self.nodes.next_sibling(reference) {
+            Some(next) if next != child => self.insert_before(next, child),
+            Some(_) => Ok(()),
+            None => self.append_child(parent, child),
+        }
+    }
+
+    /// Removes the node from the tree. It stays valid, along with its
+    /// subtree, and can be inserted again. Until then only its text nodes
+    /// have text.
+    pub fn detach(&mut self, id: NodeId) -> DomResult<()> {
+        self.check(id)?;
+        if id == self.root {
+            return Err(DomError::Error {
+                msg: "Can't detach the root node".to_string(),
+            });
+        }
+
+        self.settle();
+        self.unlink(id);
+        Ok(())
+    }
+
+    /// Detaches the node and frees it along with its subtree. Their ids are
+    /// invalid afterwards, and handed out again by `create_element` and
+    /// `create_text`.
+    pub fn remove(&mut self, id: NodeId) -> DomResult<()> {
+        self.detach(id)?;
+        self.free(id);
+        Ok(())
+    }
+
+    /// Removes the children of the node like `remove`.
+    pub fn remove_children(&mut self, id: NodeId) -> DomResult<()> {
+        self.check(id)?;
+        self.settle();
+        while let Some(child) = self.nodes.first_child(id) {
+            self.unlink(child);
+            self.free(child);
+        }
+        Ok(())
+    }
+
+    /// Puts `new` in place of `old`, which is detached.
+    pub fn replace(&mut self, old: NodeId, new: NodeId) -> DomResult<()> {
+        self.check(old)?;
+        self.check(new)?;
+        if old == new {
+            return Ok(());
+        }
+
+        if old != self.root {
+            self.insert_before(old, new)?;
+            self.unlink(old);
+            return Ok(());
+        }
+
+        if self.nodes.kind(new) != NodeKind::Element {
+            return Err(DomError::Error {
+                msg: "The root node must be an element".to_string(),
+            });
+        }
+        self.settle();
+        self.unlink(new);
+        self.stash(old);
+        self.root = new;
+        self.dirty = true;
+        if self.index.is_some() {
+            self.build_index();
+        }
+        Ok(())
+    }
+
+    pub fn rename(&mut self, id: NodeId, tag: &str) -> DomResult<()> {
+        self.check_element(id)?;
+        let tag = self.interner.intern(tag);
+        self.change_element(id, |nodes| nodes.set_tag(id, tag));
+        Ok(())
+    }
+
+    pub fn set_attr(&mut self, id: NodeId, name: &str, value: AttributeValue) -> DomResult<()> {
+        self.check_element(id)?;
+        let attribute = Attribute {
+            name: self.interner.intern(name),
+            value,
+        };
+        match indexed(name) {
+            true => self.change_element(id, |nodes| nodes.set_attribute(id, attribute)),
+            false => self.nodes.set_attribute(id, attribute),
+        }
+        Ok(())
+    }
+
+    /// Returns whether the attribute existed.
+    pub fn remove_attr(&mut self, id: NodeId, name: &str) -> DomResult<bool> {
+        self.check_element(id)?;
+        let removed = match indexed(name) {
+            true => self.change_element(id, |nodes| nodes.remove_attribute(id, name)),
+            false => self.nodes.remove_attribute(id, name),
+        };
+        Ok(removed)
+    }
+
+    /// Replaces the text of a text node, or all children of an element with
+    /// a single text node. The children are removed like `remove`.
+    pub fn set_text(&mut self, id: NodeId, text: &str) -> DomResult<()> {
+        self.check(id)?;
+        self.settle();
+        let text_id = match self.nodes.kind(id) {
+            NodeKind::Text => id,
+            NodeKind::Element => {
+                while let Some(child) = self.nodes.first_child(id) {
+                    self.unlink(child);
+                    self.free(child);
+                }
+                let text_id = self.nodes.push_text((0, 0));
+                self.nodes.append_child(id, text_id);
+                text_id
+            }
+        };
+
+        self.texts.insert(text_id, OwnedText::new(text));
+        if self.in_tree(text_id) {
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Copies the element `id` and its subtree into a document of its own,
+    /// whose text buffer only holds the text of the subtree. Annotations are
+    /// copied along.
+    pub fn clone_subtree(&self, id: NodeId) -> DomResult<Document> {
+        self.check_element(id)?;
+
+        let mut nodes = NodeArena::default();
+        let mut annotations = Annotations::default();
+        let mut texts = HashMap::new();
+        let mut stack = vec![(id, None)];
+        while let Some((id, parent)) = stack.pop() {
+            let copy = match self.nodes.kind(id) {
+                NodeKind::Element => {
+                    nodes.push_element(self.nodes.tag(id).clone(), self.nodes.attributes(id), 0)
+                }
+                NodeKind::Text => {
+                    let copy = nodes.push_text((0, 0));
+                    texts.insert(copy, OwnedText::new(DomNode::new(self, id).text()));
+                    copy
+                }
+            };
+            if let Some(parent) = parent {
+                nodes.append_child(parent, copy);
+            }
+            self.annotations.copy_node(id, &mut annotations, copy);
+
+            let mut child = self.nodes.last_child(id);
+            while let Some(id) = child {
+                stack.push((id, Some(copy)));
+                child = self.nodes.prev_sibling(id);
+            }
+        }
+
+        let root = NodeId::from_index(0);
+        let index = self
+            .index
+            .as_ref()
+            .map(|_| DocumentIndex::build(&nodes, root));
+        let mut document = Document::new(
+            Arc::new(LazyBaseBuilder::default().finalize()),
+            nodes,
+            root,
+            index,
+            self.interner.clone(),
+        );
+        document.annotations = annotations;
+        document.texts = texts;
+        document.dirty = true;
+        Ok(document)
+    }
+
+    // whether `id` is the root or one of its descendants
+    pub(crate) fn in_tree(&self, id: NodeId) -> bool {
+        let mut node = id;
+        while let Some(parent) = self.nodes.parent(node) {
+            node = parent;
+        }
+        node == self.root
+    }
+
+    // the text of the tree if it changed since the buffer was last built
+    pub(crate) fn layout(&self) -> Option<&Layout> {
+        match self.dirty {
+            true => Some(self.layout.get_or_init(|| self.build_layout())),
+            false => None,
+        }
+    }
+
+    fn check(&self, id: NodeId) -> DomResult<()> {
+        match self.nodes.contains(id) {
+            true => Ok(()),
+            false => Err(DomError::Error {
+                msg: format!("Unknown node {:?}", id),
+            }),
+        }
+    }
+
+    fn check_element(&self, id: NodeId) -> DomResult<()> {
+        self.check(id)?;
+        match self.nodes.kind(id) {
+            NodeKind::Element => Ok(()),
+            NodeKind::Text => Err(DomError::Error {
+                msg: format!("Node {:?} is not an element", id),
+            }),
+        }
+    }
+
+    fn parent_of(&self, id: NodeId) -> DomResult<NodeId> {
+        self.check(id)?;
+        self.nodes.parent(id).ok_or_else(|| DomError::Error {
+            msg: format!("Node {:?} has no parent", id),
+        })
+    }
+
+    fn check_insert(&self, parent: NodeId, child: NodeId) -> DomResult<()> {
+        self.check_element(parent)?;
+        self.check(child)?;
+        if child == self.root {
+            return Err(DomError::Error {
+                msg: "Can't move the root node".to_string(),
+            });
+        }
+
+        // the child can't become its own descendant
+        let mut node = Some(parent);
+        while let Some(id) = node {
+            if id == child {
+                return Err(DomError::Error {
+                    msg: format!("Node {:?} is an ancestor of {:?}", child, parent),
+                });
+            }
+            node = self.nodes.parent(id);
+        }
+        Ok(())
+    }
+
+    // `id` and its descendants in pre-order
+    fn subtree(&self, id: NodeId) -> Vec<NodeId> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            nodes.push(id);
+            let mut child = self.nodes.last_child(id);
+            while let Some(id) = child {
+                stack.push(id);
+                child = self.nodes.prev_sibling(id);
+            }
+        }
+        nodes
+    }
+
+    // releases the detached node `id` and its subtree
+    fn free(&mut self, id: NodeId) {
+        for node in self.subtree(id) {
+            self.texts.remove(&node);
+            self.annotations.remove_node(node);
+            self.nodes.free(node);
+        }
+    }
+
+    // takes `id` out of its parent, along with its text and index entries
+    fn unlink(&mut self, id: NodeId) {
+        if self.in_tree(id) {
+            self.stash(id);
+            if let Some(mut index) = self.index.take() {
+                for node in self.subtree(id) {
+                    if self.nodes.kind(node) == NodeKind::Element {
+                        index.remove(node, self.nodes.tag(node), self.nodes.attributes(node));
+                    }
+                }
+                self.index = Some(index);
+            }
+            self.dirty = true;
+        }
+        self.nodes.detach(id);
+    }
+
+    // `id` was just inserted, if that was into the tree its text and elements
+    // join the buffer and the index
+    fn link(&mut self, id: NodeId) {
+        if !self.in_tree(id) {
+            return;
+        }
+        self.dirty = true;
+        for node in self.subtree(id) {
+            if self.nodes.kind(node) == NodeKind::Element {
+                self.index_element(node);
+            }
+        }
+    }
+
+    // keeps the text of the text nodes below `id` aside as they leave the
+    // buffer
+    fn stash(&mut self, id: NodeId) {
+        for node in self.subtree(id) {
+            if self.nodes.kind(node) == NodeKind::Text && !self.texts.contains_key(&node) {
+                let (start, end) = self.nodes.span(node);
+                let text = OwnedText::new(&self.lazy_base.as_str()[start..end]);
+                self.texts.insert(node, text);
+            }
+        }
+    }
+
+    // applies `change` to an element of the tree, keeping its index entries
+    // up to date
+    fn change_element<F, R>(&mut self, id: NodeId, change: F) -> R
+    where
+        F: FnOnce(&mut NodeArena) -> R,
+    {
+        let indexed = self.index.is_some() && self.in_tree(id);
+        if let (true, Some(index)) = (indexed, self.index.as_mut()) {
+            index.remove(id, self.nodes.tag(id), self.nodes.attributes(id));
+        }
+        let result = change(&mut self.nodes);
+        if indexed {
+            self.index_element(id);
+        }
+        result
+    }
+
+    fn index_element(&mut self, id: NodeId) {
+        if let Some(mut index) = self.index.take() {
+            index.insert_ordered(id, self.nodes.tag(id), self.nodes.attributes(id), |a, b| {
+                self.compare_order(a, b)
+            });
+            self.index = Some(index);
+        }
+    }
+
+    // adopts a layout built since the last mutation, so the spans in `nodes`
+    // point into the current buffer again
+    fn settle(&mut self) {
+        if let Some(layout) = self.layout.take() {
+            for id in &layout.consumed {
+                self.texts.remove(id);
+            }
+            self.nodes.set_spans(layout.spans);
+            self.lazy_base = layout.base;
+            self.dirty = false;
+        }
+    }
+
+    // lays out the text of the tree the same way `DomSink` fills the buffer
+    fn build_layout(&self) -> Layout {
+        let mut base = LazyBaseBuilder::default();
+        let mut spans = vec![(0, 0); self.nodes.len()];
+        let mut consumed = Vec::new();
+        let mut pending: Option<(NodeId, &str)> = None;
+
+        // text is only appended at the next boundary, as when parsing
+        let flush = |base: &mut LazyBaseBuilder,
+                     spans: &mut Vec<(usize, usize)>,
+                     pending: &mut Option<(NodeId, &str)>| match pending.take()
+        {
+            Some((id, text)) => {
+                let start = base.append(text);
+                spans[id.index()] = (start, start + text.len());
+            }
+            None => {
+                base.append("");
+            }
+        };
+
+        let mut stack = vec![(self.root, None)];
+        while let Some((id, start)) = stack.pop() {
+            match (self.nodes.kind(id), start) {
+                (NodeKind::Text, _) => {
+                    let text = match self.texts.get(&id) {
+                        Some(text) => {
+                            consumed.push(id);
+                            text.text.as_str()
+                        }
+                        None => {
+                            let (start, end) = self.nodes.span(id);
+                            &self.lazy_base.as_str()[start..end]
+                        }
+                    };
+                    if pending.is_some() {
+                        flush(&mut base, &mut spans, &mut pending);
+                    }
+                    pending = Some((id, text));
+                }
+                (NodeKind::Element, None) => {
+                    flush(&mut base, &mut spans, &mut pending);
+                    stack.push((id, Some(base.len())));
+
+                    let mut child = self.nodes.last_child(id);
+                    while let Some(id) = child {
+                        stack.push((id, None));
+                        child = self.nodes.prev_sibling(id);
+                    }
+                }
+                (NodeKind::Element, Some(start)) => {
+                    flush(&mut base, &mut spans, &mut pending);
+                    spans[id.index()] = (start, base.len());
+                }
+            }
+        }
+        if pending.is_some() {
+            flush(&mut base, &mut spans, &mut pending);
+        }
+
+        Layout {
+            base: Arc::new(base.finalize()),
+            spans,
+            consumed,
+        }
+    }
+}
+
+// changes to these attributes move the element within the index
+fn indexed(name: &str) -> bool {
+    matches!(name, "id" | "class")
+}
+
+impl DomNode<'_> {
+    /// The subtree of the element as a standalone document, see
+    /// `Document::clone_subtree`.
+    pub fn to_document(&self) -> DomResult<Document> {
+        self.document().clone_subtree(self.node_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{DomBuilder, DomNode};
+
+    fn parse(input: &str) -> Document {
+        let builder = DomBuilder::new();
+        builder.feed(input);
+        builder.finalize().unwrap()
+    }
+
+    fn by_tag(document: &Document, tag: &str) -> NodeId {
+        fn find(node: DomNode, tag: &str) -> Option<NodeId> {
+            match node.tag().as_ref() == tag {
+                true => Some(node.node_id()),
+                false => node.children().find_map(|child| find(child, tag)),
+            }
+        }
+        find(document.root(), tag).unwrap()
+    }
+
+    // tags and texts of the tree, e.g. `div(p(a)b(c))`
+    fn outline(node: DomNode) -> String {
+        match node.is_text() {
+            true => node.text().to_string(),
+            false => {
+                let children = node.child_nodes().map(outline).collect::<String>();
+                format!("{}({})", node.tag(), children)
+            }
+        }
+    }
+
+    fn ids(nodes: Vec<DomNode<'_>>) -> Vec<NodeId> {
+        nodes.iter().map(|node| node.node_id()).collect()
+    }
+
+    // text of the whole document, as parsing `input` would lay it out
+    fn assert_text(document: &Document, input: &str) {
+        let parsed = parse(input);
+        assert_eq!(document.lazy_base().as_str(), parsed.lazy_base().as_str());
+        assert_eq!(outline(document.root()), outline(parsed.root()));
+        assert_eq!(document.root().text(), parsed.root().text());
+    }
+
+    fn literal(value: &str) -> AttributeValue {
+        AttributeValue::Literal(value.to_string())
+    }
+
+    #[test]
+    fn detached_nodes_keep_their_text() {
+        let mut document = parse("<div><p>one</p><b>two</b></div>");
+        let p = by_tag(&document, "p");
+        document.detach(p).unwrap();
+        assert_text(&document, "<div><b>two</b></div>");
+        let text = document.nodes().first_child(p).unwrap();
+        assert_eq!(document.node(text).unwrap().text(), "one");
+        assert_eq!(document.node(p).unwrap().text(), "");
+
+        let b = by_tag(&document, "b");
+        document.insert_after(b, p).unwrap();
+        assert_text(&document, "<div><b>two</b><p>one</p></div>");
+        assert_eq!(document.find_text("one").len(), 1);
+        assert_eq!(document.find_text("two").len(), 1);
+    }
+
+    #[test]
+    fn text_is_rebuilt_after_changes() {
+        let mut document = parse("<div><p>one</p><p>two</p></div>");
+        let p = by_tag(&document, "p");
+        document.set_text(p, "uno").unwrap();
+        let text = document.create_text("tres");
+        document.append_child(document.root, text).unwrap();
+        assert_text(&document, "<div><p>uno</p><p>two</p>tres</div>");
+
+        // reads in between don't lose changes made afterwards
+        document.set_text(text, "three").unwrap();
+        assert_text(&document, "<div><p>uno</p><p>two</p>three</div>");
+        let matches = document.find_text("three");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].node.node_id(), text);
+        assert_eq!(matches[0].text().to_string(), "three");
+    }
+
+    #[test]
+    fn replacing_the_root() {
+        let mut document = parse("<div><p>a</p></div>");
+        let section = document.create_element("section");
+        let text = document.create_text("b");
+        document.append_child(section, text).unwrap();
+        assert!(document.replace(document.root, text).is_err());
+
+        let old = document.root;
+        document.replace(old, section).unwrap();
+        assert_text(&document, "<section>b</section>");
+        assert_eq!(document.node(old).unwrap().text(), "");
+        assert!(document.append_child(section, old).is_ok());
+        assert_text(&document, "<section>b<div><p>a</p></div></section>");
+    }
+
+    #[test]
+    fn index_stays_in_document_order() {
+        let mut document = parse(r#"<div><p class="a">1</p><p>2</p><p class="a">3</p></div>"#);
+        document.build_index();
+        let ps = ids(document.get_elements_by_tag("p"));
+
+        // moving the first paragraph to the end
+        document.append_child(document.root, ps[0]).unwrap();
+        assert_eq!(
+            ids(document.get_elements_by_tag("p")),
+            [ps[1], ps[2], ps[0]]
+        );
+        assert_eq!(ids(document.get_elements_by_class("a")), [ps[2], ps[0]]);
+
+        document.set_attr(ps[1], "class", literal("b a")).unwrap();
+        assert_eq!(
+            ids(document.get_elements_by_class("a")),
+            [ps[1], ps[2], ps[0]]
+        );
+        document.set_attr(ps[2], "id", literal("x")).unwrap();
+        assert_eq!(document.get_element_by_id("x").unwrap().node_id(), ps[2]);
+        document.remove_attr(ps[2], "class").unwrap();
+        assert_eq!(ids(document.get_elements_by_class("a")), [ps[1], ps[0]]);
+
+        document.rename(ps[1], "span").unwrap();
+        assert_eq!(ids(document.get_elements_by_tag("p")), [ps[2], ps[0]]);
+        assert_eq!(ids(document.get_elements_by_tag("span")), [ps[1]]);
+
+        document.detach(ps[2]).unwrap();
+        assert!(document.get_element_by_id("x").is_none());
+        assert_eq!(ids(document.get_elements_by_tag("p")), [ps[0]]);
+    }
+
+    #[test]
+    fn removed_nodes_are_reused() {
+        let mut document = parse("<div><p><b>a</b></p><i>b</i></div>");
+        let len = document.nodes().len();
+        let p = by_tag(&document, "p");
+        document.remove(p).unwrap();
+        assert!(document.node(p).is_none());
+        assert!(document.detach(p).is_err());
+
+        for _ in 0..3 {
+            let b = document.create_element("b");
+            let text = document.create_text("c");
+            document.append_child(b, text).unwrap();
+            document.append_child(document.root, b).unwrap();
+        }
+        assert_eq!(document.nodes().len(), len + 3);
+        assert_text(&document, "<div><i>b</i><b>c</b><b>c</b><b>c</b></div>");
+    }
+
+    #[test]
+    fn replaced_children_are_freed() {
+        let mut document = parse("<div><p><b>a</b> b</p></div>");
+        let p = by_tag(&document, "p");
+        let b = by_tag(&document, "b");
+        let len = document.nodes().len();
+        document.annotations_mut().set_flag(b, "seen");
+        for i in 0..100 {
+            document.set_text(p, &i.to_string()).unwrap();
+        }
+        assert_eq!(document.nodes().len(), len);
+        assert!(document.node(b).is_none());
+        assert_eq!(document.annotations().flagged("seen").count(), 0);
+        assert_text(&document, "<div><p>99</p></div>");
+
+        document.remove_children(document.root).unwrap();
+        assert!(document.node(p).is_none());
+        document.create_element("b");
+        assert_eq!(document.nodes().len(), len);
+    }
+
+    #[test]
+    fn detached_text_is_indexed_once() {
+        let mut document = parse("<div><p>one</p></div>");
+        let text = document.create_text("two");
+        let node = document.node(text).unwrap();
+        let first = node.text_content();
+        assert_eq!(first.as_str(), "two");
+        assert!(std::ptr::eq(first.as_str(), node.text_content().as_str()));
+    }
+
+    #[test]
+    fn attribute_storage_is_compacted() {
+        let mut document = parse(r#"<div><p title="keep" data-x="1">a</p></div>"#);
+        let p = by_tag(&document, "p");
+        for i in 0..1000 {
+            document
+                .set_attr(document.root, "data-n", literal(&i.to_string()))
+                .unwrap();
+            document
+                .set_attr(p, "data-x", literal(&"x".repeat(i % 7)))
+                .unwrap();
+            document.remove_attr(p, "data-y").unwrap();
+            document
+                .set_attr(p, "data-y", AttributeValue::Exists)
+                .unwrap();
+            document.remove_attr(p, "data-y").unwrap();
+        }
+        let node = document.node(p).unwrap();
+        assert_eq!(node.attr("title"), Some("keep"));
+        assert_eq!(node.attr("data-x"), Some("x".repeat(999 % 7).as_str()));
+        assert!(!node.has_attr("data-y"));
+        assert_eq!(document.root().attr("data-n"), Some("999"));
+        let (slots, bytes) = document.nodes().attribute_storage();
+        assert!(slots < 16 && bytes < 64);
+    }
+
+    #[test]
+    fn clone_subtree_of_a_mutated_document() {
+        let mut document = parse("<div><p>a <b>b</b></p><p>c</p></div>");
+        let b = by_tag(&document, "b");
+        document.set_text(b, "bold").unwrap();
+        let p = by_tag(&document, "p");
+
+        let copy = document.clone_subtree(p).unwrap();
+        assert_eq!(
+            copy.root().outer_html(),
+            parse("<p>a <b>bold</b></p>").root().outer_html()
+        );
+        assert_text(&copy, "<p>a <b>bold</b></p>");
+        assert_eq!(copy.find_text("bold").len(), 1);
+    }
+
+    #[test]
+    fn cloned_documents_are_independent() {
+        let builder = DomBuilder::new();
+        builder.enable_index();
+        builder.feed(r#"<div><p id="a" class="x">one</p><p id="b">two</p></div>"#);
+        let mut document = builder.finalize().unwrap();
+        let a = by_tag(&document, "p");
+        document.annotations_mut().set_flag(a, "seen");
+        let html = document.html();
+
+        let mut copy = document.clone();
+        assert_eq!(copy.html(), html);
+        copy.set_text(a, "changed").unwrap();
+        copy.set_attr(a, "id", literal("c")).unwrap();
+        copy.remove(copy.get_element_by_id("b").unwrap().node_id())
+            .unwrap();
+        copy.annotations_mut().set_flag(a, "copied");
+        copy.annotations_mut().clear_flag(a, "seen");
+
+        assert_eq!(document.html(), html);
+        assert_eq!(document.root().inner_text(), "one\n\ntwo");
+        assert_eq!(document.get_element_by_id("a").unwrap().node_id(), a);
+        assert!(document.get_element_by_id("b").is_some());
+        assert!(document.get_element_by_id("c").is_none());
+        assert_eq!(document.get_elements_by_class("x").len(), 1);
+        assert!(document.annotations().has_flag(a, "seen"));
+        assert!(!document.annotations().has_flag(a, "copied"));
+
+        assert_eq!(copy.root().inner_text(), "changed");
+        assert_eq!(copy.get_element_by_id("c").unwrap().node_id(), a);
+        assert!(copy.get_element_by_id("a").is_none());
+        assert!(copy.get_element_by_id("b").is_none());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    dom::{arena::NodeId, DomNode},
+    xpath::filter::{DescendantIterator, RevDescendantIterator},
+};
+
+// Element navigation skips text nodes, the `*_node` variants don't.
+impl<'a> DomNode<'a> {
+    fn view(&self, id: Option<NodeId>) -> Option<DomNode<'a>> {
+        id.map(|id| DomNode::new(self.document(), id))
+    }
+
+    pub fn parent(&self) -> Option<DomNode<'a>> {
+        self.view(self.document().nodes.parent(self.node_id()))
+    }
+
+    pub fn first_child_node(&self) -> Option<DomNode<'a>> {
+        self.view(self.document().nodes.first_child(self.node_id()))
+    }
+
+    pub fn last_child_node(&self) -> Option<DomNode<'a>> {
+        self.view(self.document().nodes.last_child(self.node_id()))
+    }
+
+    pub fn next_node(&self) -> Option<DomNode<'a>> {
+        self.view(self.document().nodes.next_sibling(self.node_id()))
+    }
+
+    pub fn prev_node(&self) -> Option<DomNode<'a>> {
+        self.view(self.document().nodes.prev_sibling(self.node_id()))
+    }
+
+    pub fn first_child(&self) -> Option<DomNode<'a>> {
+        let first = self.first_child_node()?;
+        match first.is_element() {
+            true => Some(first),
+            false => first.next_sibling(),
+        }
+    }
+
+    pub fn last_child(&self) -> Option<DomNode<'a>> {
+        let last = self.last_child_node()?;
+        match last.is_element() {
+            true => Some(last),
+            false => last.prev_sibling(),
+        }
+    }
+
+    pub fn next_sibling(&self) -> Option<DomNode<'a>> {
+        let mut node = self.next_node();
+        while let Some(next) = node {
+            if next.is_element() {
+                return Some(next);
+            }
+            node = next.next_node();
+        }
+        None
+    }
+
+    pub fn prev_sibling(&self) -> Option<DomNode<'a>> {
+        let mut node = self.prev_node();
+        while let Some(prev) = node {
+            if prev.is_element() {
+                return Some(prev);
+            }
+            node = prev.prev_node();
+        }
+        None
+    }
+
+    /// Element children.
+    pub fn children(&self) -> Children<'a> {
+        Children {
+            next: self.first_child(),
+        }
+    }
+
+    /// All children, text nodes included.
+    pub fn child_nodes(&self) -> ChildNodes<'a> {
+        ChildNodes {
+            next: self.first_child_node(),
+        }
+    }
+
+    /// Ancestors of the node, starting with its parent.
+    pub fn ancestors(&self) -> Ancestors<'a> {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// The node itself or its nearest ancestor with the tag `tag`.
+    pub fn closest(&self, tag: &str) -> Option<DomNode<'a>> {
+        std::iter::once(*self)
+            .chain(self.ancestors())
+            .find(|node| node.is_element() && node.tag().as_ref() == tag)
+    }
+
+    /// Position among the element children of the parent.
+    pub fn index_in_parent(&self) -> Option<usize> {
+        let parent = self.parent()?;
+        parent
+            .children()
+            .position(|child| child.node_id() == self.node_id())
+    }
+
+    /// The node and its descendant elements in document order.
+    pub fn descendants(&self) -> DescendantIterator<'a> {
+        DescendantIterator::from_root(*self)
+    }
+
+    /// `descendants` in reverse, ending with the node itself.
+    pub fn rev_descendants(&self) -> RevDescendantIterator<'a> {
+        RevDescendantIterator::from_root(*self)
+    }
+
+    /// Number of ancestors, the root has a depth of 0.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Children<'a> {
+    next: Option<DomNode<'a>>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = DomNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next_sibling();
+        Some(node)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChildNodes<'a> {
+    next: Option<DomNode<'a>>,
+}
+
+impl<'a> Iterator for ChildNodes<'a> {
+    type Item = DomNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next_node();
+        Some(node)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ancestors<'a> {
+    next: Option<DomNode<'a>>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = DomNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.parent();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::{Document, DomBuilder, DomNode};
+
+    fn parse() -> Document {
+        DomBuilder::parse("<div><p>a <b>b</b> c</p><ul><li>x</li><li>y <i>z</i></li></ul></div>")
+            .unwrap()
+    }
+
+    fn tags<'a>(nodes: impl Iterator<Item = DomNode<'a>>) -> Vec<String> {
+        nodes.map(|node| node.tag().to_string()).collect()
+    }
+
+    fn find<'a>(document: &'a Document, tag: &str) -> DomNode<'a> {
+        document
+            .root()
+            .descendants()
+            .find(|node| node.tag().as_ref() == tag)
+            .unwrap()
+    }
+
+    #[test]
+    fn parents_and_children() {
+        let document = parse();
+        let root = document.root();
+        assert!(root.parent().is_none());
+        assert_eq!(tags(root.children()), ["p", "ul"]);
+        assert_eq!(
+            tags(root.descendants()),
+            ["div", "p", "b", "ul", "li", "li", "i"]
+        );
+
+        let b = find(&document, "b");
+        assert_eq!(b.parent().unwrap().tag().as_ref(), "p");
+        let p = b.parent().unwrap();
+        assert_eq!(p.parent().unwrap().node_id(), root.node_id());
+        assert_eq!(p.first_child().unwrap().node_id(), b.node_id());
+        assert_eq!(p.last_child().unwrap().node_id(), b.node_id());
+        assert_eq!(p.child_nodes().count(), 3);
+        assert!(p.first_child_node().unwrap().is_text());
+        assert_eq!(b.first_child_node().unwrap().text(), "b");
+        assert!(b.first_child().is_none());
+    }
+
+    #[test]
+    fn siblings_skip_text() {
+        let document = parse();
+        let b = find(&document, "b");
+        assert!(b.next_sibling().is_none());
+        assert!(b.prev_sibling().is_none());
+        assert_eq!(b.next_node().unwrap().text(), "c");
+        assert_eq!(b.prev_node().unwrap().text(), "a");
+
+        let ul = find(&document, "ul");
+        let p = ul.prev_sibling().unwrap();
+        assert_eq!(p.tag().as_ref(), "p");
+        assert_eq!(p.next_sibling().unwrap().node_id(), ul.node_id());
+        assert!(ul.next_sibling().is_none());
+
+        let first = ul.first_child().unwrap();
+        let second = first.next_sibling().unwrap();
+        assert_eq!(second.prev_sibling().unwrap().node_id(), first.node_id());
+        assert_eq!(ul.last_child().unwrap().node_id(), second.node_id());
+    }
+
+    #[test]
+    fn ancestors_and_closest() {
+        let document = parse();
+        let i = find(&document, "i");
+        assert_eq!(tags(i.ancestors()), ["li", "ul", "div"]);
+        assert_eq!(document.root().ancestors().count(), 0);
+
+        assert_eq!(i.closest("i").unwrap().node_id(), i.node_id());
+        assert_eq!(i.closest("ul").unwrap().tag().as_ref(), "ul");
+        assert_eq!(
+            i.closest("div").unwrap().node_id(),
+            document.root().node_id()
+        );
+        assert!(i.closest("p").is_none());
+
+        // text nodes find their elements too
+        let text = i.first_child_node().unwrap();
+        assert_eq!(
+            text.closest("li").unwrap().node_id(),
+            i.parent().unwrap().node_id()
+        );
+        assert!(text.closest("#text").is_none());
+    }
+
+    #[test]
+    fn positions_and_depths() {
+        let document = parse();
+        let root = document.root();
+        assert_eq!(root.index_in_parent(), None);
+        assert_eq!(root.depth(), 0);
+
+        let ul = find(&document, "ul");
+        assert_eq!(ul.index_in_parent(), Some(1));
+        let li = ul.last_child().unwrap();
+        assert_eq!(li.index_in_parent(), Some(1));
+        assert_eq!(li.depth(), 2);
+        // text nodes don't count as positions
+        let i = find(&document, "i");
+        assert_eq!(i.index_in_parent(), Some(0));
+        assert_eq!(i.depth(), 3);
+        assert_eq!(i.first_child_node().unwrap().depth(), 4);
+    }
+}
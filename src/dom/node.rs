@@ -1,4 +1,10 @@
+use std::fmt;
+
 use crate::{
+    dom::{
+        arena::{Attributes, NodeId, NodeKind},
+        Document,
+    },
     utils::{LazyStr, SharedStr},
     xpath::{filter::XPathFilter, XPath},
 };
@@ -15,25 +21,97 @@ pub enum AttributeValue {
     Literal(String),
 }
 
-#[derive(Debug)]
-pub struct DomNode {
-    pub tag: SharedStr,
-    pub attributes: Vec<Attribute>,
-    pub text_content: LazyStr,
-    pub children: Vec<DomNode>,
+/// Attribute of a node in a `Document`, its value borrowed from the buffer
+/// holding the values of all attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeRef<'a> {
+    pub name: &'a SharedStr,
+    /// `None` for an attribute without a value, like `<input disabled>`.
+    pub value: Option<&'a str>,
+}
+
+/// View of a node stored in a `Document`.
+#[derive(Clone, Copy)]
+pub struct DomNode<'a> {
+    doc: &'a Document,
+    id: NodeId,
 }
 
-impl DomNode {
-    pub fn new(tag: SharedStr) -> Self {
-        DomNode {
-            tag,
-            attributes: Vec::new(),
-            text_content: LazyStr::default(),
-            children: Vec::new(),
+impl<'a> DomNode<'a> {
+    pub(crate) fn new(doc: &'a Document, id: NodeId) -> Self {
+        DomNode { doc, id }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn document(&self) -> &'a Document {
+        self.doc
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.doc.nodes.kind(self.id)
+    }
+
+    pub fn is_element(&self) -> bool {
+        self.kind() == NodeKind::Element
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.kind() == NodeKind::Text
+    }
+
+    /// Tag name of an element, `#text` for text nodes.
+    pub fn tag(&self) -> &'a SharedStr {
+        self.doc.nodes.tag(self.id)
+    }
+
+    pub fn attributes(&self) -> Attributes<'a> {
+        self.doc.nodes.attributes(self.id)
+    }
+
+    /// Text of the node and all of its descendants. Detached elements have
+    /// no text until they are inserted again, detached text nodes keep theirs.
+    pub fn text_content(&self) -> LazyStr {
+        let detached = self
+            .doc
+            .texts
+            .get(&self.id)
+            .filter(|_| !self.doc.in_tree(self.id));
+        if let Some(text) = detached {
+            return LazyStr::new(text.base().clone(), 0, text.text.len());
         }
+        let (start, end) = self.doc.span(self.id);
+        LazyStr::new(self.doc.lazy_base().clone(), start, end)
     }
 
-    pub fn query<'a>(&'a self, xpath: &'a XPath) -> XPathFilter<'a> {
-        XPathFilter::new_with_node(xpath, self)
+    /// Like `text_content`, borrowed from the document without touching the
+    /// reference count.
+    pub fn text(&self) -> &'a str {
+        if let Some(text) = self.doc.texts.get(&self.id) {
+            return &text.text;
+        }
+        let (start, end) = self.doc.span(self.id);
+        &self.doc.lazy_base().as_str()[start..end]
+    }
+
+    pub fn query(&self, xpath: &'a XPath) -> XPathFilter<'a> {
+        XPathFilter::new_with_node(xpath, *self)
+    }
+}
+
+impl fmt::Debug for DomNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind() {
+            NodeKind::Element => f
+                .debug_struct("DomNode")
+                .field("tag", self.tag())
+                .field("attributes", &self.attributes())
+                .field("text_content", &self.text())
+                .field("children", &self.children().collect::<Vec<_>>())
+                .finish(),
+            NodeKind::Text => f.debug_tuple("Text").field(&self.text()).finish(),
+        }
     }
 }
@@ -0,0 +1,298 @@
+use std::{
+    cmp::Ordering,
+    fmt::Write,
+    hash::{Hash, Hasher},
+};
+
+use crate::dom::{arena::NodeId, Document, DomNode, NodeKind};
+
+// Node ids never change while the node exists, detaching a node only
+// unlinks it, so they are also the identity of `DomNode`s within a document.
+impl Document {
+    /// Order of `a` and `b` in the document, ancestors before their
+    /// descendants. Nodes of different detached trees are ordered by their
+    /// roots.
+    pub fn compare_order(&self, a: NodeId, b: NodeId) -> Ordering {
+        if a == b {
+            return Ordering::Equal;
+        }
+
+        let (path_a, path_b) = (self.path_from_root(a), self.path_from_root(b));
+        if path_a[0] != path_b[0] {
+            return path_a[0].cmp(&path_b[0]);
+        }
+
+        let split = path_a
+            .iter()
+            .zip(&path_b)
+            .position(|(a, b)| a != b)
+            .unwrap_or(path_a.len().min(path_b.len()));
+        match (path_a.get(split), path_b.get(split)) {
+            (Some(&a), Some(&b)) => {
+                let mut sibling = self.nodes.next_sibling(a);
+                while let Some(id) = sibling {
+                    if id == b {
+                        return Ordering::Less;
+                    }
+                    sibling = self.nodes.next_sibling(id);
+                }
+                Ordering::Greater
+            }
+            // the shorter path is an ancestor of the other
+            _ => path_a.len().cmp(&path_b.len()),
+        }
+    }
+
+    /// Whether `ancestor` is a proper ancestor of `id`.
+    pub fn is_ancestor_of(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut parent = self.nodes.parent(id);
+        while let Some(node) = parent {
+            if node == ancestor {
+                return true;
+            }
+            parent = self.nodes.parent(node);
+        }
+        false
+    }
+
+    /// The deepest node that is `a` or an ancestor of it, and likewise of
+    /// `b`. `None` for nodes of different trees.
+    pub fn common_ancestor(&self, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let (path_a, path_b) = (self.path_from_root(a), self.path_from_root(b));
+        path_a
+            .iter()
+            .zip(&path_b)
+            .take_while(|(a, b)| a == b)
+            .last()
+            .map(|(id, _)| *id)
+    }
+
+    /// Finds the node of a path made by `DomNode::path`.
+    pub fn resolve_path(&self, path: &str) -> Option<DomNode<'_>> {
+        let mut steps = path.strip_prefix('/')?.split('/');
+
+        let (tag, pos) = parse_path_step(steps.next()?)?;
+        let root = self.root();
+        if root.tag().as_ref() != tag || pos != 1 {
+            return None;
+        }
+
+        steps.try_fold(root, |node, step| {
+            let (tag, pos) = parse_path_step(step)?;
+            let mut candidates: Box<dyn Iterator<Item = DomNode>> = match tag {
+                "text()" => Box::new(node.child_nodes().filter(|child| child.is_text())),
+                _ => Box::new(node.children().filter(|child| child.tag().as_ref() == tag)),
+            };
+            candidates.nth(pos.checked_sub(1)?)
+        })
+    }
+
+    // ancestors of `id` starting with the root of its tree, `id` included
+    fn path_from_root(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![id];
+        while let Some(parent) = self.nodes.parent(path[path.len() - 1]) {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+}
+
+// `tag` or `tag[pos]`, positions start at 1
+fn parse_path_step(step: &str) -> Option<(&str, usize)> {
+    match step.strip_suffix(']') {
+        Some(rest) => {
+            let (tag, pos) = rest.split_once('[')?;
+            Some((tag, pos.parse().ok()?))
+        }
+        None => Some((step, 1)),
+    }
+}
+
+impl<'a> DomNode<'a> {
+    pub fn compare_order(&self, other: &DomNode) -> Ordering {
+        self.document()
+            .compare_order(self.node_id(), other.node_id())
+    }
+
+    pub fn is_ancestor_of(&self, other: &DomNode) -> bool {
+        self.document()
+            .is_ancestor_of(self.node_id(), other.node_id())
+    }
+
+    pub fn common_ancestor(&self, other: &DomNode) -> Option<DomNode<'a>> {
+        let id = self
+            .document()
+            .common_ancestor(self.node_id(), other.node_id())?;
+        Some(DomNode::new(self.document(), id))
+    }
+
+    /// Absolute path like `/html/body/div[3]/p[2]`, a position is added where
+    /// siblings share the tag. Text nodes end in `text()[n]`.
+    pub fn path(&self) -> String {
+        let mut nodes = std::iter::once(*self)
+            .chain(self.ancestors())
+            .collect::<Vec<_>>();
+        nodes.reverse();
+
+        let mut path = String::new();
+        for node in nodes {
+            let name = match node.kind() {
+                NodeKind::Element => node.tag().as_ref(),
+                NodeKind::Text => "text()",
+            };
+            let _ = match same_name_siblings(node) {
+                (0, 0) => write!(path, "/{}", name),
+                (before, _) => write!(path, "/{}[{}]", name, before + 1),
+            };
+        }
+        path
+    }
+}
+
+// siblings of `node` it shares a name with, before and after it
+fn same_name_siblings(node: DomNode) -> (usize, usize) {
+    let Some(parent) = node.parent() else {
+        return (0, 0);
+    };
+    let mut same = parent
+        .child_nodes()
+        .filter(|child| child.kind() == node.kind() && child.tag() == node.tag())
+        .map(|child| child.node_id());
+    let before = same.by_ref().take_while(|id| *id != node.node_id()).count();
+    (before, same.count())
+}
+
+// nodes are equal when they are the same node of the same document
+impl PartialEq for DomNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.document(), other.document()) && self.node_id() == other.node_id()
+    }
+}
+
+impl Eq for DomNode<'_> {}
+
+impl Hash for DomNode<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.document(), state);
+        self.node_id().hash(state);
+    }
+}
+
+/// Document order, nodes of different documents are grouped per document.
+impl Ord for DomNode<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (doc, other_doc) = (self.document(), other.document());
+        match std::ptr::eq(doc, other_doc) {
+            true => doc.compare_order(self.node_id(), other.node_id()),
+            false => (doc as *const Document).cmp(&(other_doc as *const Document)),
+        }
+    }
+}
+
+impl PartialOrd for DomNode<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cmp::Ordering,
+        collections::{BTreeSet, HashSet},
+    };
+
+    use crate::dom::{Document, DomBuilder, DomNode};
+
+    const INPUT: &str =
+        "<html><body><div><p>a</p><p>b <i>c</i> d</p></div><div><p>e</p></div></body></html>";
+
+    fn parse() -> Document {
+        DomBuilder::parse(INPUT).unwrap()
+    }
+
+    fn ps(document: &Document) -> Vec<DomNode<'_>> {
+        document
+            .root()
+            .descendants()
+            .filter(|node| node.tag().as_ref() == "p")
+            .collect()
+    }
+
+    #[test]
+    fn orders_siblings_and_ancestors() {
+        let document = parse();
+        let ps = ps(&document);
+        let div = ps[0].parent().unwrap();
+
+        assert_eq!(ps[0].compare_order(&ps[1]), Ordering::Less);
+        assert_eq!(ps[1].compare_order(&ps[0]), Ordering::Greater);
+        assert_eq!(ps[2].compare_order(&ps[1]), Ordering::Greater);
+        assert_eq!(ps[1].compare_order(&ps[1]), Ordering::Equal);
+        // ancestors come before their descendants
+        assert_eq!(div.compare_order(&ps[1]), Ordering::Less);
+        assert_eq!(ps[0].compare_order(&div), Ordering::Greater);
+        assert_eq!(document.root().compare_order(&ps[2]), Ordering::Less);
+
+        let mut shuffled = vec![ps[2], div, ps[0], document.root(), ps[1]];
+        shuffled.sort();
+        assert_eq!(shuffled, [document.root(), div, ps[0], ps[1], ps[2]]);
+    }
+
+    #[test]
+    fn ancestors_and_common_ancestors() {
+        let document = parse();
+        let ps = ps(&document);
+        let (div, body) = (
+            ps[0].parent().unwrap(),
+            document.root().children().next().unwrap(),
+        );
+
+        assert!(div.is_ancestor_of(&ps[1]));
+        assert!(body.is_ancestor_of(&ps[2]));
+        assert!(!div.is_ancestor_of(&ps[2]));
+        assert!(!ps[0].is_ancestor_of(&ps[0]));
+        assert_eq!(ps[0].common_ancestor(&ps[1]), Some(div));
+        assert_eq!(ps[0].common_ancestor(&ps[2]), Some(body));
+        assert_eq!(ps[0].common_ancestor(&div), Some(div));
+    }
+
+    #[test]
+    fn paths_resolve_to_their_node() {
+        let document = parse();
+        assert_eq!(ps(&document)[1].path(), "/html/body/div[1]/p[2]");
+        assert_eq!(ps(&document)[2].path(), "/html/body/div[2]/p");
+
+        let nodes = document.root().descendants().collect::<Vec<_>>();
+        let texts = nodes
+            .iter()
+            .flat_map(|node| node.child_nodes())
+            .filter(|node| node.is_text())
+            .collect::<Vec<_>>();
+        assert_eq!(texts[2].path(), "/html/body/div[1]/p[2]/text()[2]");
+        for node in nodes.iter().chain(&texts) {
+            assert_eq!(document.resolve_path(&node.path()), Some(*node));
+        }
+
+        assert!(document.resolve_path("/html/body/div[3]").is_none());
+        assert!(document.resolve_path("/body").is_none());
+        assert!(document.resolve_path("html").is_none());
+    }
+
+    #[test]
+    fn nodes_are_identities() {
+        let document = parse();
+        let other = parse();
+        let ps = ps(&document);
+
+        let set = ps.iter().chain(&ps).copied().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&document.node(ps[1].node_id()).unwrap()));
+        // the same id in another document is another node
+        assert!(!set.contains(&other.node(ps[1].node_id()).unwrap()));
+
+        let ordered = ps.iter().rev().copied().collect::<BTreeSet<_>>();
+        assert_eq!(ordered.into_iter().collect::<Vec<_>>(), ps);
+    }
+}
@@ -1,17 +1,25 @@
-use std::{cell::RefCell, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    sync::Arc,
+};
 
 use crate::{
     dom::{
         error::{DomError, DomResult},
-        parser::{sink::DomSink, tokenizer::Tokenizer},
-        Document,
+        parser::{
+            interface::Tag, node_filter::FilterAction, sink::DomSink, stop::StopCondition,
+            tokenizer::Tokenizer,
+        },
+        Document, PartialDocument,
     },
-    utils::CharQueue,
+    utils::{CharQueue, Interner, LocalPool},
 };
 
 pub struct DomBuilder {
-    tokenizer: RefCell<Tokenizer>,
+    tokenizer: RefCell<Tokenizer<DomSink>>,
     sink: Arc<DomSink>,
+    // input other than whitespace was fed after the stop condition was met
+    ignored_input: Cell<bool>,
 }
 
 impl DomBuilder {
@@ -21,17 +29,83 @@ impl DomBuilder {
         builder.finalize()
     }
 
+    /// Parses until `condition` is met, leaving the rest of `input` unread.
+    pub fn parse_until(input: &str, condition: StopCondition) -> DomResult<PartialDocument> {
+        let builder = Self::new();
+        builder.stop_when(condition)?;
+        builder.feed(input);
+        builder.finalize_partial()
+    }
+
     pub fn new() -> Self {
         let sink = Arc::new(DomSink::new());
         let tokenizer = RefCell::new(Tokenizer::new(sink.clone()));
-        Self { tokenizer, sink }
+        Self {
+            tokenizer,
+            sink,
+            ignored_input: false.into(),
+        }
+    }
+
+    /// Registers a filter deciding per start tag whether its subtree is kept,
+    /// dropped or reduced to its text. Filtered elements are never built.
+    pub fn add_filter<F>(&self, filter: F)
+    where
+        F: Fn(&Tag) -> FilterAction + Send + 'static,
+    {
+        self.sink.add_filter(Box::new(filter));
+    }
+
+    /// Builds the id, tag and class indexes of the document while parsing.
+    pub fn enable_index(&self) {
+        self.sink.enable_index();
+    }
+
+    /// Interns tag and attribute names with `interner`, which the document
+    /// keeps for its mutations. Call before feeding any input.
+    pub fn set_interner(&self, interner: Arc<dyn Interner>) {
+        self.tokenizer.borrow_mut().set_interner(interner.clone());
+        self.sink.set_interner(interner);
+    }
+
+    /// Interns names in a pool owned by the document rather than the global
+    /// one, so parallel parses don't contend and the names are freed with it.
+    pub fn local_interning(&self) {
+        self.set_interner(Arc::new(LocalPool::new()));
+    }
+
+    pub fn stop_when(&self, condition: StopCondition) -> DomResult<()> {
+        self.sink.stop_when(condition)
+    }
+
+    // true once the stop condition is met, further input is ignored
+    pub fn is_stopped(&self) -> bool {
+        self.sink.is_stopped()
     }
 
     pub fn feed(&self, input: &str) {
-        self.tokenizer.borrow_mut().feed(CharQueue::from_str(input));
+        match self.is_stopped() {
+            false => self.tokenizer.borrow_mut().feed(CharQueue::from_str(input)),
+            true if !input.trim().is_empty() => self.ignored_input.set(true),
+            true => (),
+        }
+    }
+
+    /// The document is `truncated` if the parse stopped with input other than
+    /// whitespace left unread.
+    pub fn finalize_partial(self) -> DomResult<PartialDocument> {
+        let truncated = self.is_stopped()
+            && (self.ignored_input.get() || self.tokenizer.borrow().has_pending());
+        self.finalize().map(|document| PartialDocument {
+            document,
+            truncated,
+        })
     }
 
     pub fn finalize(self) -> DomResult<Document> {
+        if !self.is_stopped() {
+            self.tokenizer.borrow_mut().end();
+        }
         std::mem::drop(self.tokenizer);
         match Arc::try_unwrap(self.sink) {
             Ok(sink) => Ok(sink.end()?),
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{dom::node::Attribute, utils::SharedStr};
+use crate::{dom::node::AttributeRef, utils::SharedStr};
 
 #[derive(Debug, Clone)]
 pub enum TagKind {
@@ -13,7 +13,17 @@ pub enum TagKind {
 pub struct Tag {
     pub kind: TagKind,
     pub name: SharedStr,
-    pub attrs: Vec<Attribute>,
+    pub attrs: Vec<TagAttribute>,
+    // values of all attributes back to back, copied into the document as is
+    pub values: String,
+}
+
+/// Attribute of a tag token, its value a range of `Tag::values`.
+#[derive(Debug, Clone)]
+pub struct TagAttribute {
+    pub name: SharedStr,
+    /// `None` for an attribute without a value.
+    pub value: Option<(usize, usize)>,
 }
 
 impl Default for Tag {
@@ -22,10 +32,25 @@ impl Default for Tag {
             kind: TagKind::StartTag,
             name: Arc::from(""),
             attrs: Vec::new(),
+            values: String::new(),
         }
     }
 }
 
+impl Tag {
+    pub fn attributes(&self) -> impl Iterator<Item = AttributeRef<'_>> + Clone {
+        self.attrs.iter().map(|attribute| AttributeRef {
+            name: &attribute.name,
+            value: attribute.value.map(|(start, end)| &self.values[start..end]),
+        })
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<AttributeRef<'_>> {
+        self.attributes()
+            .find(|attribute| attribute.name.as_ref() == name)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Token {
     Tag(Tag),
@@ -38,6 +63,19 @@ pub enum Token {
     EndOfInput,
 }
 
+/// Byte range of a token in the input fed to the tokenizer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
 // #[derive(Debug, Clone)]
 // pub enum Token<'a> {
 //     DoctypeToken(&'a str),
@@ -52,3 +90,19 @@ pub enum TokenSinkResult {
     Special(SharedStr),
     Suspend,
 }
+
+/// Receives the tokens produced by the DOM `Tokenizer`.
+///
+/// Returning `TokenSinkResult::Special(name)` for a start tag switches the
+/// tokenizer into raw text mode until `</name>` is found (as `DomSink` does for
+/// `script` and `style`). Returning `Suspend` stops the current run.
+pub trait TokenSink {
+    fn process_token(&self, token: Token) -> TokenSinkResult;
+
+    /// Called by the tokenizer with the source position of every token.
+    /// Sinks that don't care about positions only need `process_token`.
+    fn process_spanned(&self, token: Token, span: Span) -> TokenSinkResult {
+        let _ = span;
+        self.process_token(token)
+    }
+}
@@ -1,6 +1,15 @@
 pub mod builder;
 pub mod interface;
-mod sink;
+pub mod node_filter;
+pub mod sink;
+pub mod stop;
 pub mod tokenizer;
+pub mod tokens;
 
 pub use builder::DomBuilder;
+pub use interface::{Span, Tag, TagKind, Token, TokenSink, TokenSinkResult};
+pub use node_filter::{FilterAction, NodeFilter};
+pub use sink::{DomSink, SpannedToken, TokenQueue};
+pub use stop::StopCondition;
+pub use tokenizer::Tokenizer;
+pub use tokens::{tokens, Tokens};
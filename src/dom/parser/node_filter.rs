@@ -0,0 +1,124 @@
+use crate::dom::parser::interface::Tag;
+
+/// What to do with an element, and everything inside it, during parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Keep,
+    /// Skip the element and its contents, text included.
+    Drop,
+    /// Skip the element and its descendants but keep their text in the parent.
+    TextOnly,
+}
+
+impl FilterAction {
+    // the stricter action wins when filters disagree
+    pub fn merge(self, other: FilterAction) -> FilterAction {
+        match (self, other) {
+            (FilterAction::Drop, _) | (_, FilterAction::Drop) => FilterAction::Drop,
+            (FilterAction::TextOnly, _) | (_, FilterAction::TextOnly) => FilterAction::TextOnly,
+            _ => FilterAction::Keep,
+        }
+    }
+}
+
+/// Decides per start tag whether its subtree is materialized. Filters are
+/// `Send` so sinks, and the streams built on them, can change threads.
+pub type NodeFilter = Box<dyn Fn(&Tag) -> FilterAction + Send>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{Document, DomBuilder, DomNode};
+
+    fn parse_with<F>(input: &str, filter: F) -> Document
+    where
+        F: Fn(&Tag) -> FilterAction + Send + 'static,
+    {
+        let builder = DomBuilder::new();
+        builder.add_filter(filter);
+        builder.feed(input);
+        builder.finalize().unwrap()
+    }
+
+    fn by_name(name: &'static str, action: FilterAction) -> impl Fn(&Tag) -> FilterAction {
+        move |tag| match tag.name.as_ref() == name {
+            true => action,
+            false => FilterAction::Keep,
+        }
+    }
+
+    // tags of a subtree, e.g. `div(p()p())`
+    fn outline(node: DomNode) -> String {
+        let children = node.children().map(outline).collect::<String>();
+        format!("{}({})", node.tag(), children)
+    }
+
+    #[test]
+    fn merge_prefers_the_stricter_action() {
+        use FilterAction::*;
+        assert_eq!(Keep.merge(Keep), Keep);
+        assert_eq!(Keep.merge(TextOnly), TextOnly);
+        assert_eq!(TextOnly.merge(Drop), Drop);
+        assert_eq!(Drop.merge(Keep), Drop);
+    }
+
+    #[test]
+    fn drop_skips_subtree_and_text() {
+        let input = "<div><p>a</p><script>var x = '<p>';</script><p>b</p></div>";
+        let document = parse_with(input, by_name("script", FilterAction::Drop));
+        assert_eq!(outline(document.root()), "div(p()p())");
+        assert_eq!(document.root().text().trim(), "a b");
+    }
+
+    #[test]
+    fn drop_applies_to_nested_and_empty_elements() {
+        let input = "<div><nav><ul><li>x</li></ul><br/></nav><img/><p>a</p></div>";
+        let document = parse_with(input, |tag: &Tag| match tag.name.as_ref() {
+            "nav" | "img" => FilterAction::Drop,
+            _ => FilterAction::Keep,
+        });
+        assert_eq!(outline(document.root()), "div(p())");
+        assert_eq!(document.root().text().trim(), "a");
+    }
+
+    #[test]
+    fn text_only_keeps_text_in_parent() {
+        let input = "<div><p>a <span>b <i>c</i></span> d</p></div>";
+        let document = parse_with(input, by_name("span", FilterAction::TextOnly));
+        let p = document.root().children().next().unwrap();
+        assert_eq!(p.children().count(), 0);
+        assert_eq!(p.text(), "a b c d");
+    }
+
+    #[test]
+    fn dropped_inside_text_only_is_dropped() {
+        let input = "<div><section>a<aside>b</aside>c</section></div>";
+        let document = parse_with(input, |tag: &Tag| match tag.name.as_ref() {
+            "section" => FilterAction::TextOnly,
+            "aside" => FilterAction::Drop,
+            _ => FilterAction::Keep,
+        });
+        let root = document.root();
+        assert_eq!(root.children().count(), 0);
+        let text = root.text();
+        assert!(text.contains('a') && text.contains('c'));
+        assert!(!text.contains('b'));
+    }
+
+    #[test]
+    fn filters_see_attribute_values() {
+        let input = r#"<div><p class="ad">a</p><p hidden>b</p><p class="">c</p></div>"#;
+        let document = parse_with(input, |tag: &Tag| {
+            match tag.attribute("class").map(|a| a.value) == Some(Some("ad"))
+                || tag.attribute("hidden").is_some()
+            {
+                true => FilterAction::Drop,
+                false => FilterAction::Keep,
+            }
+        });
+        assert_eq!(
+            document.root().outer_html(),
+            r#"<div><p class="">c</p></div>"#
+        );
+    }
+}
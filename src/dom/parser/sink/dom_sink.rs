@@ -7,100 +7,174 @@ use std::{
 
 use crate::{
     dom::{
+        arena::{NodeArena, NodeId, NodeKind},
         error::{DomError, DomResult},
-        node::DomNode,
-        parser::interface::{Tag, TagKind, Token, TokenSinkResult},
+        index::DocumentIndex,
+        parser::{
+            interface::{Tag, TagKind, Token, TokenSink, TokenSinkResult},
+            node_filter::{FilterAction, NodeFilter},
+            stop::{StopCondition, Stopper},
+        },
         Document,
     },
-    utils::{LazyBase, LazyStr, SharedPool},
+    utils::{Interner, LazyBaseBuilder, SharedPool, SharedStr},
 };
 
-impl DomNode {
-    pub fn init(tag: Tag, lazy_base: Arc<LazyBase>) -> Self {
-        DomNode {
-            tag: tag.name,
-            attributes: tag.attrs,
-            text_content: LazyStr::init(lazy_base),
-            children: Vec::new(),
-        }
-    }
-
-    pub fn finalize(&mut self) {
-        self.text_content.finalize();
-    }
-}
-
 pub struct DomSink {
-    lazy_base: Arc<LazyBase>,
-    open_stack: RefCell<Vec<DomNode>>,
+    lazy_base: RefCell<LazyBaseBuilder>,
+    nodes: RefCell<NodeArena>,
+    // top level elements, a valid document has exactly one
+    roots: RefCell<Vec<NodeId>>,
+    open_stack: RefCell<Vec<NodeId>>,
     text_content: RefCell<String>,
     error: Cell<Option<DomError>>,
+    stopper: RefCell<Option<Stopper>>,
+    stopped: Cell<bool>,
+    filters: RefCell<Vec<NodeFilter>>,
+    // open elements that are filtered out and not materialized
+    skipped: RefCell<Vec<(SharedStr, FilterAction)>>,
+    index: RefCell<Option<DocumentIndex>>,
+    interner: RefCell<Arc<dyn Interner>>,
 }
 
 impl DomSink {
-    fn special_tags() -> &'static HashSet<&'static str> {
+    pub(crate) fn special_tags() -> &'static HashSet<&'static str> {
         static SPECIAL_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
 
         SPECIAL_TAGS.get_or_init(|| HashSet::from(["script", "style"]))
     }
 
     pub fn new() -> Self {
-        let open_stack = vec![DomNode::new(SharedPool::get_or_intern("root"))];
-
         DomSink {
-            lazy_base: Arc::new(LazyBase::default()),
-            open_stack: open_stack.into(),
+            lazy_base: LazyBaseBuilder::default().into(),
+            nodes: NodeArena::default().into(),
+            roots: Vec::new().into(),
+            open_stack: Vec::new().into(),
             text_content: String::new().into(),
             error: None.into(),
+            stopper: None.into(),
+            stopped: false.into(),
+            filters: Vec::new().into(),
+            skipped: Vec::new().into(),
+            index: None.into(),
+            interner: RefCell::new(SharedPool::instance().clone()),
         }
     }
 
-    pub fn process_token(&self, token: Token) -> TokenSinkResult {
-        match token {
-            Token::Tag(tag) => match tag.kind {
-                TagKind::StartTag => self.handle_start(tag),
-                TagKind::EndTag => self.handle_end(tag),
-                TagKind::EmptyTag => self.handle_empty(tag),
-            },
-            Token::Text(text) => self.handle_text(text),
-            Token::EndOfInput => TokenSinkResult::Suspend,
-            Token::InvalidChar(c) => {
-                self.error.set(Some(DomError::ParseError {
-                    exp: format!("Invalid char '{}'", c),
-                }));
-                TokenSinkResult::Suspend
-            }
-            _ => TokenSinkResult::Continue,
+    /// Indexes elements by id, tag and class while they are built.
+    pub fn enable_index(&self) {
+        self.index.replace(Some(DocumentIndex::default()));
+    }
+
+    /// Interner handed to the document for names added by mutations.
+    pub fn set_interner(&self, interner: Arc<dyn Interner>) {
+        self.interner.replace(interner);
+    }
+
+    pub fn add_filter(&self, filter: NodeFilter) {
+        self.filters.borrow_mut().push(filter);
+    }
+
+    fn filter(&self, tag: &Tag) -> FilterAction {
+        match self.skipped.borrow().last() {
+            // everything inside a dropped element is dropped as well
+            Some((_, FilterAction::Drop)) => FilterAction::Drop,
+            skipped => self
+                .filters
+                .borrow()
+                .iter()
+                .fold(FilterAction::Keep, |action, filter| {
+                    action.merge(filter(tag))
+                })
+                .merge(skipped.map_or(FilterAction::Keep, |_| FilterAction::TextOnly)),
         }
     }
 
+    pub fn stop_when(&self, condition: StopCondition) -> DomResult<()> {
+        self.stopper.replace(Some(Stopper::new(condition)?));
+        Ok(())
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.get()
+    }
+
+    fn observe_start(&self, tag: &Tag) {
+        if let Some(stopper) = self.stopper.borrow_mut().as_mut() {
+            stopper.start(tag);
+        }
+    }
+
+    fn observe_end(&self, name: &str) -> bool {
+        let stop = self
+            .stopper
+            .borrow_mut()
+            .as_mut()
+            .is_some_and(|stopper| stopper.end(name));
+        self.stopped.set(stop);
+        stop
+    }
+
+    // whether an end tag named `name` closes an open element, filtered out
+    // ones included
+    fn closes_element(&self, name: &SharedStr) -> bool {
+        let nodes = self.nodes.borrow();
+        self.skipped.borrow().iter().any(|(open, _)| open == name)
+            || self
+                .open_stack
+                .borrow()
+                .iter()
+                .any(|id| nodes.tag(*id) == name)
+    }
+
     fn handle_start(&self, tag: Tag) -> TokenSinkResult {
         self.finalize_text();
 
-        // handles special tags differently (e.g. script, style)
-        match DomSink::special_tags().contains(tag.name.as_ref()) {
-            true => {
-                let name = tag.name.clone();
-                self.add_node(tag);
-                TokenSinkResult::Special(name)
+        let name = tag.name.clone();
+        match self.filter(&tag) {
+            FilterAction::Keep => self.add_node(tag),
+            action => {
+                self.skipped.borrow_mut().push((name.clone(), action));
+                TokenSinkResult::Continue
             }
-            false => self.add_node(tag),
+        };
+
+        // handles special tags differently (e.g. script, style)
+        match DomSink::special_tags().contains(name.as_ref()) {
+            true => TokenSinkResult::Special(name),
+            false => TokenSinkResult::Continue,
         }
     }
 
     fn handle_end(&self, tag: Tag) -> TokenSinkResult {
         self.finalize_text();
 
-        let matches;
-        if self.open_stack.borrow().len() == 1 {
-            return self.revert_stack();
-        } else {
-            matches = match self.open_stack.borrow().last() {
-                Some(top) => tag.name == top.tag,
-                _ => return TokenSinkResult::Suspend,
+        // stray end tags are ignored, filtered out elements stay open
+        if !self.closes_element(&tag.name) {
+            return match self.open_stack.borrow().is_empty() {
+                true => self.revert_stack(),
+                false => TokenSinkResult::Continue,
             };
         }
 
+        {
+            let mut skipped = self.skipped.borrow_mut();
+            match skipped.iter().rposition(|(name, _)| *name == tag.name) {
+                Some(idx) => {
+                    skipped.truncate(idx);
+                    return TokenSinkResult::Continue;
+                }
+                // closes an element outside of the skipped ones
+                None => skipped.clear(),
+            }
+        }
+
+        let top = self.open_stack.borrow().last().copied();
+        let matches = match top {
+            Some(top) => tag.name == *self.nodes.borrow().tag(top),
+            None => return self.revert_stack(),
+        };
+
         match matches {
             true => self.finalize_node(),
             false => {
@@ -113,11 +187,19 @@ impl DomSink {
     fn handle_empty(&self, tag: Tag) -> TokenSinkResult {
         self.finalize_text();
 
+        if self.filter(&tag) != FilterAction::Keep {
+            return TokenSinkResult::Continue;
+        }
+
         self.add_node(tag);
         self.finalize_node()
     }
 
     fn handle_text(&self, str: String) -> TokenSinkResult {
+        if let Some((_, FilterAction::Drop)) = self.skipped.borrow().last() {
+            return TokenSinkResult::Continue;
+        }
+
         let mut text_content = self.text_content.borrow_mut();
         match text_content.borrow().len() {
             0 => {
@@ -134,32 +216,47 @@ impl DomSink {
     }
 
     fn add_node(&self, tag: Tag) -> TokenSinkResult {
-        self.open_stack
-            .borrow_mut()
-            .push(DomNode::init(tag, self.lazy_base.clone()));
+        let mut nodes = self.nodes.borrow_mut();
+        let id = nodes.push_element(
+            tag.name.clone(),
+            tag.attributes(),
+            self.lazy_base.borrow().len(),
+        );
+        if let Some(index) = self.index.borrow_mut().as_mut() {
+            index.insert(id, nodes.tag(id), nodes.attributes(id));
+        }
+        match self.open_stack.borrow().last() {
+            Some(parent) => nodes.append_child(*parent, id),
+            None => self.roots.borrow_mut().push(id),
+        }
+
+        self.open_stack.borrow_mut().push(id);
         TokenSinkResult::Continue
     }
 
     fn finalize_node(&self) -> TokenSinkResult {
-        let mut open_stack = self.open_stack.borrow_mut();
-        let last = open_stack.pop();
-        let parent = open_stack.last_mut();
-        match (last, parent) {
-            (Some(mut last), Some(parent)) => {
-                last.finalize();
-                parent.children.push(last);
+        match self.open_stack.borrow_mut().pop() {
+            Some(id) => {
+                self.nodes
+                    .borrow_mut()
+                    .set_span_end(id, self.lazy_base.borrow().len());
                 TokenSinkResult::Continue
             }
-            _ => TokenSinkResult::Suspend,
+            None => TokenSinkResult::Suspend,
         }
     }
 
+    // reopens the last closed elements, which ignores a stray end tag
     fn revert_stack(&self) -> TokenSinkResult {
+        let nodes = self.nodes.borrow();
         let mut stack = self.open_stack.borrow_mut();
-        while let Some(node) = stack.last_mut() {
-            match node.children.pop() {
-                Some(child) => stack.push(child),
-                None => break,
+        let mut next = self.roots.borrow().last().copied();
+        while let Some(id) = next {
+            stack.push(id);
+
+            next = nodes.last_child(id);
+            while let Some(child) = next.filter(|child| nodes.kind(*child) != NodeKind::Element) {
+                next = nodes.prev_sibling(child);
             }
         }
         TokenSinkResult::Continue
@@ -177,30 +274,96 @@ impl DomSink {
             }
         }
 
-        self.lazy_base.as_ref().append(&self.text_content.take());
+        let text = self.text_content.take();
+        let start = self.lazy_base.borrow_mut().append(&text);
+        if let (false, Some(parent)) = (text.is_empty(), self.open_stack.borrow().last()) {
+            let mut nodes = self.nodes.borrow_mut();
+            let id = nodes.push_text((start, start + text.len()));
+            nodes.append_child(*parent, id);
+        }
     }
 
     pub fn end(self) -> DomResult<Document> {
+        // a stopped parse closes whatever is still open
+        if self.stopped.get() {
+            self.finalize_text();
+            while !self.open_stack.borrow().is_empty() {
+                self.finalize_node();
+            }
+        }
+
         match self.error.take() {
             Some(e) => Err(e),
             None => {
-                let mut root = self.open_stack.borrow_mut().pop().unwrap();
-                let node = root.children.pop();
-                match (self.open_stack.borrow().len(), root.children.len(), node) {
-                    (0, 0, Some(root)) => {
-                        let lazy_base = self.lazy_base;
-                        lazy_base.as_ref().borrow_mut().finalize();
-
-                        Ok(Document { root, lazy_base })
-                    }
-                    (_, _, Some(_)) => Err(DomError::Error {
-                        msg: "Multiple root nodes".to_string(),
-                    }),
-                    (_, _, None) => Err(DomError::Error {
+                if let Some(top) = self.open_stack.borrow().last() {
+                    return Err(DomError::Error {
+                        msg: format!("Unclosed node '{}'", self.nodes.borrow().tag(*top)),
+                    });
+                }
+
+                match self.roots.take().as_slice() {
+                    [root] => Ok(Document::new(
+                        Arc::new(self.lazy_base.into_inner().finalize()),
+                        self.nodes.into_inner(),
+                        *root,
+                        self.index.into_inner(),
+                        self.interner.into_inner(),
+                    )),
+                    [] => Err(DomError::Error {
                         msg: "No root node exists".to_string(),
                     }),
+                    _ => Err(DomError::Error {
+                        msg: "Multiple root nodes".to_string(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl TokenSink for DomSink {
+    fn process_token(&self, token: Token) -> TokenSinkResult {
+        if self.stopped.get() {
+            return TokenSinkResult::Suspend;
+        }
+
+        match token {
+            Token::Tag(tag) => {
+                if !matches!(tag.kind, TagKind::EndTag) {
+                    self.observe_start(&tag);
+                }
+
+                let name = tag.name.clone();
+                let (result, closed) = match tag.kind {
+                    TagKind::StartTag => return self.handle_start(tag),
+                    TagKind::EndTag => {
+                        let closed = self.closes_element(&name);
+                        (self.handle_end(tag), closed)
+                    }
+                    TagKind::EmptyTag => (self.handle_empty(tag), true),
+                };
+
+                // stray end tags are ignored by the tree, and so by the stopper
+                match closed && self.observe_end(&name) {
+                    true => TokenSinkResult::Suspend,
+                    false => result,
                 }
             }
+            Token::Text(text) => self.handle_text(text),
+            Token::EndOfInput => TokenSinkResult::Suspend,
+            Token::InvalidChar(c) => {
+                self.error.set(Some(DomError::ParseError {
+                    exp: format!("Invalid char '{}'", c),
+                }));
+                TokenSinkResult::Suspend
+            }
+            _ => TokenSinkResult::Continue,
         }
     }
 }
+
+impl Default for DomSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
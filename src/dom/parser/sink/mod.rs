@@ -1,3 +1,5 @@
 pub mod dom_sink;
+pub mod token_queue;
 
 pub use dom_sink::DomSink;
+pub use token_queue::{SpannedToken, TokenQueue};
@@ -0,0 +1,66 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::dom::parser::{
+    interface::{Span, Tag, TagKind, Token, TokenSink, TokenSinkResult},
+    sink::DomSink,
+};
+
+/// A token together with its byte range in the input.
+#[derive(Debug, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// Sink that buffers every token for later consumption. It can be shared
+/// between threads, so a `Tokens` iterator can be sent to another thread.
+/// Invalid chars are queued like any other token, the tokenizer skips them
+/// and carries on with the rest of the input.
+#[derive(Debug, Default)]
+pub struct TokenQueue {
+    queue: Mutex<VecDeque<SpannedToken>>,
+}
+
+impl TokenQueue {
+    pub fn new() -> Self {
+        TokenQueue::default()
+    }
+
+    pub fn pop(&self) -> Option<SpannedToken> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+}
+
+impl TokenSink for TokenQueue {
+    fn process_token(&self, token: Token) -> TokenSinkResult {
+        self.process_spanned(token, Span::default())
+    }
+
+    fn process_spanned(&self, token: Token, span: Span) -> TokenSinkResult {
+        let result = match &token {
+            Token::EndOfInput => return TokenSinkResult::Suspend,
+            Token::Tag(Tag {
+                kind: TagKind::StartTag,
+                name,
+                ..
+            }) if DomSink::special_tags().contains(name.as_ref()) => {
+                TokenSinkResult::Special(name.clone())
+            }
+            _ => TokenSinkResult::Continue,
+        };
+
+        self.queue
+            .lock()
+            .unwrap()
+            .push_back(SpannedToken { token, span });
+        result
+    }
+}
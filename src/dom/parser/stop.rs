@@ -0,0 +1,206 @@
+use crate::{
+    dom::{
+        error::{DomError, DomResult},
+        parser::interface::Tag,
+    },
+    xpath::{stream::StreamMatcher, XPath},
+};
+
+/// When to stop parsing before the end of input.
+#[derive(Debug, Clone)]
+pub enum StopCondition {
+    /// After the first end tag named `name`, e.g. `</head>`.
+    AfterEnd(String),
+    /// Once every expression has at least `count` complete matches. The
+    /// expressions are limited to what `XPathStream` supports.
+    Matches { xpaths: Vec<XPath>, count: usize },
+}
+
+impl StopCondition {
+    /// Stops once every expression has matched one element.
+    pub fn found(xpaths: Vec<XPath>) -> Self {
+        StopCondition::Matches { xpaths, count: 1 }
+    }
+}
+
+pub(crate) struct MatchCounter {
+    matcher: StreamMatcher,
+    // depths of matched elements that are still open
+    pending: Vec<usize>,
+    done: usize,
+}
+
+pub(crate) enum Stopper {
+    AfterEnd(String),
+    Matches {
+        counters: Vec<MatchCounter>,
+        count: usize,
+    },
+}
+
+impl Stopper {
+    pub fn new(condition: StopCondition) -> DomResult<Self> {
+        match condition {
+            StopCondition::AfterEnd(name) => Ok(Stopper::AfterEnd(name)),
+            StopCondition::Matches { xpaths, count } => {
+                let counters = xpaths
+                    .iter()
+                    .map(|xpath| {
+                        StreamMatcher::new(xpath)
+                            .map(|matcher| MatchCounter {
+                                matcher,
+                                pending: Vec::new(),
+                                done: 0,
+                            })
+                            .map_err(|e| DomError::Error { msg: e.to_string() })
+                    })
+                    .collect::<DomResult<Vec<_>>>()?;
+                Ok(Stopper::Matches { counters, count })
+            }
+        }
+    }
+
+    pub fn start(&mut self, tag: &Tag) {
+        if let Stopper::Matches { counters, .. } = self {
+            for counter in counters.iter_mut() {
+                if counter.matcher.start(tag) {
+                    counter.pending.push(counter.matcher.depth());
+                }
+            }
+        }
+    }
+
+    /// Returns true once the condition is met. Only called for end tags that
+    /// close an element, stray ones never reach the stopper.
+    pub fn end(&mut self, name: &str) -> bool {
+        match self {
+            Stopper::AfterEnd(end) => end == name,
+            Stopper::Matches { counters, count } => {
+                for counter in counters.iter_mut() {
+                    counter.matcher.end(name);
+                    let depth = counter.matcher.depth();
+                    while counter.pending.last().is_some_and(|d| *d > depth) {
+                        counter.pending.pop();
+                        counter.done += 1;
+                    }
+                }
+                counters.iter().all(|c| c.done >= *count)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dom::{parser::FilterAction, DomBuilder, DomNode},
+        xpath::XPathBuilder,
+    };
+
+    fn found(xpaths: &[&str]) -> StopCondition {
+        StopCondition::found(
+            xpaths
+                .iter()
+                .map(|xpath| XPathBuilder::parse(xpath).unwrap())
+                .collect(),
+        )
+    }
+
+    fn count(node: DomNode) -> usize {
+        node.children().map(|child| 1 + count(child)).sum()
+    }
+
+    #[test]
+    fn stops_after_end_tag() {
+        let input = "<html><head><title>t</title></head><body><p>a</p></body></html>";
+        let partial =
+            DomBuilder::parse_until(input, StopCondition::AfterEnd("head".into())).unwrap();
+        assert!(partial.truncated);
+        assert_eq!(partial.document.root().children().count(), 1);
+    }
+
+    #[test]
+    fn stops_after_first_heading() {
+        // tag and attribute names may contain digits
+        let input = r#"<html><h1 data-v2="a">t</h1><h2>u</h2></html>"#;
+        let partial = DomBuilder::parse_until(input, found(&["//h1"])).unwrap();
+        assert!(partial.truncated);
+        let h1 = partial.document.root().children().next().unwrap();
+        assert_eq!(h1.tag().as_ref(), "h1");
+        let attribute = h1.attributes().next().unwrap();
+        assert_eq!(attribute.name.as_ref(), "data-v2");
+        assert_eq!(partial.document.root().children().count(), 1);
+    }
+
+    #[test]
+    fn stray_end_tags_are_ignored() {
+        let input = "<html><body></head><p>a</p></body></html>";
+        let partial =
+            DomBuilder::parse_until(input, StopCondition::AfterEnd("head".into())).unwrap();
+        assert!(!partial.truncated);
+        assert_eq!(count(partial.document.root()), 2);
+    }
+
+    #[test]
+    fn counts_matches_despite_stray_end_tags() {
+        // the stray </div> must not close the open div for the matcher
+        let input = "<html><div><p>a</p></div></div><div><p>b</p></div><p>c</p></html>";
+        let partial = DomBuilder::parse_until(input, found(&["/div/p"])).unwrap();
+        assert!(partial.truncated);
+        assert_eq!(partial.document.root().children().count(), 1);
+
+        let partial = DomBuilder::parse_until(input, found(&["//div", "//p"])).unwrap();
+        assert!(partial.truncated);
+        assert_eq!(partial.document.root().children().count(), 1);
+    }
+
+    #[test]
+    fn stray_end_tags_keep_filtered_elements_open() {
+        let builder = DomBuilder::new();
+        builder.add_filter(|tag| match tag.name.as_ref() {
+            "aside" => FilterAction::Drop,
+            _ => FilterAction::Keep,
+        });
+        builder.stop_when(found(&["/p"])).unwrap();
+        builder.feed("<html><aside><p>a</p></y><p>b</p></aside><p>c</p><p>d</p></html>");
+
+        let partial = builder.finalize_partial().unwrap();
+        assert!(partial.truncated);
+        let root = partial.document.root();
+        let texts = root.children().map(|p| p.text()).collect::<Vec<_>>();
+        assert_eq!(texts, ["c"]);
+    }
+
+    #[test]
+    fn stop_on_last_end_tag_is_not_truncated() {
+        let input = "<html><p>a</p></html>\n";
+        let partial =
+            DomBuilder::parse_until(input, StopCondition::AfterEnd("html".into())).unwrap();
+        assert!(!partial.truncated);
+
+        let partial = DomBuilder::parse_until("<p>a<b>b</b></p>", found(&["//p"])).unwrap();
+        assert!(!partial.truncated);
+
+        let builder = DomBuilder::new();
+        builder.stop_when(found(&["//p"])).unwrap();
+        builder.feed("<html><p>a</p>");
+        builder.feed("</html>");
+        assert!(builder.is_stopped());
+        // only </html> is left
+        assert!(builder.finalize_partial().unwrap().truncated);
+    }
+
+    #[test]
+    fn input_after_stop_is_truncated() {
+        let builder = DomBuilder::new();
+        builder
+            .stop_when(StopCondition::AfterEnd("p".into()))
+            .unwrap();
+        builder.feed("<html><p>a</p>");
+        builder.feed("<p>b</p></html>");
+        let partial = builder.finalize_partial().unwrap();
+        assert!(partial.truncated);
+        assert_eq!(partial.document.root().children().count(), 1);
+    }
+}
@@ -1,36 +1,36 @@
 use crate::{
-    dom::{
-        node::{Attribute, AttributeValue},
-        parser::interface::{Tag, TagKind, Token},
-    },
+    dom::parser::interface::{Tag, TagAttribute, TagKind, Token},
     tup,
     utils::{
         parser::{
-            alpha0, alpha1, alt, char, delimited, error::PError, many0, map, preceded, tag,
-            tag_no_case, take_till, take_while1, traits::Parser, trimmed, tuple,
+            alpha0, alpha1, alt, char, delimited, error::PError, map, name1, preceded, tag,
+            tag_no_case, take_while1, traits::Parser, trimmed, tuple,
         },
-        ParseQueue, SharedPool,
+        Interner, ParseQueue,
     },
 };
 
-pub fn parse_token() -> impl Parser<Output = Token> {
+pub fn parse_token(interner: &dyn Interner) -> impl Parser<Output = Token> + '_ {
     alt(tup!(
-        map(parse_tag(), |tag| Token::Tag(tag)),
+        map(parse_tag(interner), |tag| Token::Tag(tag)),
         map(parse_doctype(), |doctype| Token::Doctype(doctype)),
         map(parse_comment(), |comment| Token::Comment(comment)),
         map(parse_text(), |text| Token::Text(text))
     ))
 }
 
-pub fn parse_special<'a>(special: &'a str) -> impl Parser<Output = Token> + 'a {
+pub fn parse_special<'a>(
+    special: &'a str,
+    interner: &'a dyn Interner,
+) -> impl Parser<Output = Token> + 'a {
     alt(tup!(
-        map(parse_special_end(special), |tag| Token::Tag(tag)),
+        map(parse_special_end(special, interner), |tag| Token::Tag(tag)),
         map(parse_text(), |text| Token::Text(text))
     ))
 }
 
-fn parse_tag() -> impl Parser<Output = Tag> {
-    alt(tup!(parse_start_empty(), parse_end()))
+fn parse_tag(interner: &dyn Interner) -> impl Parser<Output = Tag> + '_ {
+    alt(tup!(parse_start_empty(interner), parse_end(interner)))
 }
 
 pub fn parse_text() -> impl Parser<Output = String> {
@@ -66,79 +66,108 @@ fn parse_comment() -> impl Parser<Output = String> {
 }
 
 // Parsed together due to similarity in early structure
-pub fn parse_start_empty() -> impl Parser<Output = Tag> {
+pub fn parse_start_empty(interner: &dyn Interner) -> impl Parser<Output = Tag> + '_ {
     map(
         preceded(
             char('<'),
             trimmed(tuple(tup!(
-                map(alpha1, |name| { SharedPool::get_or_intern(name) }),
-                parse_attrs(),
+                map(name1, |name| interner.intern(&name)),
+                parse_attrs(interner),
                 alt(tup!(
                     map(char('>'), |_| TagKind::StartTag),
                     map(tag("/>"), |_| TagKind::EmptyTag)
                 ))
             ))),
         ),
-        |(name, (attrs, kind))| Tag { kind, name, attrs },
+        |(name, ((attrs, values), kind))| Tag {
+            kind,
+            name,
+            attrs,
+            values,
+        },
     )
 }
 
-fn parse_end() -> impl Parser<Output = Tag> {
+fn parse_end(interner: &dyn Interner) -> impl Parser<Output = Tag> + '_ {
     map(
         delimited(
             tag("</"),
-            trimmed(map(alpha1, |name| SharedPool::get_or_intern(name))),
+            trimmed(map(name1, |name| interner.intern(&name))),
             char('>'),
         ),
         |name| Tag {
             kind: TagKind::EndTag,
             name,
             attrs: Vec::new(),
+            values: String::new(),
         },
     )
 }
 
-fn parse_special_end<'a>(special: &'a str) -> impl Parser<Output = Tag> + 'a {
+fn parse_special_end<'a>(
+    special: &'a str,
+    interner: &'a dyn Interner,
+) -> impl Parser<Output = Tag> + 'a {
     map(
         delimited(
             tag("</"),
-            map(tag(special), |name| SharedPool::get_or_intern(name)),
+            map(tag(special), |name| interner.intern(name)),
             char('>'),
         ),
         |name| Tag {
             kind: TagKind::EndTag,
             name,
             attrs: Vec::new(),
+            values: String::new(),
         },
     )
 }
 
-fn parse_attrs() -> impl Parser<Output = Vec<Attribute>> {
-    many0(trimmed(parse_attr()))
+fn parse_attrs(interner: &dyn Interner) -> impl Parser<Output = (Vec<TagAttribute>, String)> + '_ {
+    move |input: &mut ParseQueue| {
+        let mut attrs = Vec::new();
+        let mut values = String::new();
+        loop {
+            let len = values.len();
+            let attribute = trimmed(parse_attr(interner, &mut values)).parse(input);
+            match attribute {
+                Ok(attribute) => attrs.push(attribute),
+                Err(_) => {
+                    values.truncate(len);
+                    break;
+                }
+            }
+        }
+        Ok((attrs, values))
+    }
 }
 
-fn parse_attr() -> impl Parser<Output = Attribute> {
-    |input: &mut ParseQueue| {
+// the value is appended to `values` rather than allocated on its own
+fn parse_attr<'a>(
+    interner: &'a dyn Interner,
+    values: &'a mut String,
+) -> impl Parser<Output = TagAttribute> + 'a {
+    move |input: &mut ParseQueue| {
         let name = map(
-            take_while1(|c: char| matches!(c, 'a'..='z' | 'A'..='Z' | '-' | ':' )),
-            |name| SharedPool::get_or_intern(name),
+            // digits are allowed like in tag names, see `name1`
+            take_while1(|c: char| c.is_alphanumeric() || matches!(c, '-' | ':')),
+            |name| interner.intern(&name),
         )
         .parse(input)?;
         let eq = trimmed(char('=')).parse(input);
         let value = match eq.is_ok() {
-            true => AttributeValue::Literal(parse_str().parse(input)?),
-            false => AttributeValue::Exists,
+            true => {
+                let q = alt(tup!(char('\''), char('"'))).parse(input)?;
+                let start = values.len();
+                while let Some(c) = input.peek().filter(|c| *c != q) {
+                    values.push(c);
+                    input.dequeue();
+                }
+                char(q).parse(input)?;
+                Some((start, values.len()))
+            }
+            false => None,
         };
-        Ok(Attribute { name, value })
-    }
-}
-
-fn parse_str() -> impl Parser<Output = String> {
-    move |input: &mut ParseQueue| {
-        alt(tup!(char('\''), char('"'))).parse(input).and_then(|q| {
-            let content = take_till(|c| c == q).parse(input)?;
-            char(q).parse(input)?;
-            Ok(content)
-        })
+        Ok(TagAttribute { name, value })
     }
 }
@@ -1,13 +1,10 @@
 use std::sync::Arc;
 
 use crate::{
-    dom::parser::{
-        interface::{Token, TokenSinkResult},
-        sink::dom_sink::DomSink,
-    },
+    dom::parser::interface::{Span, Token, TokenSink, TokenSinkResult},
     utils::{
         parser::{error::PError, traits::Parser},
-        CharQueue, ParseQueue, SharedStr,
+        CharQueue, Interner, ParseQueue, SharedPool, SharedStr,
     },
 };
 
@@ -23,60 +20,109 @@ pub enum State {
     Special(SharedStr),
 }
 
-pub struct Tokenizer {
-    sink: Arc<DomSink>,
+pub struct Tokenizer<Sink> {
+    sink: Arc<Sink>,
     state: State,
+    // input not yet turned into tokens, kept between feeds
+    input: CharQueue,
+    // byte offset of the start of `input` in everything fed so far
+    offset: usize,
+    // `input` is a held back text token, which only changes once a tag
+    // delimiter arrives
+    held: bool,
+    at_end: bool,
+    interner: Arc<dyn Interner>,
 }
 
-impl Tokenizer {
-    pub fn new(sink: Arc<DomSink>) -> Self {
+impl<Sink: TokenSink> Tokenizer<Sink> {
+    pub fn new(sink: Arc<Sink>) -> Self {
         Tokenizer {
             sink,
             state: State::Base,
+            input: CharQueue::default(),
+            offset: 0,
+            held: false,
+            at_end: false,
+            interner: SharedPool::instance().clone(),
         }
     }
 
+    /// Interns tag and attribute names with `interner` instead of the global
+    /// pool.
+    pub fn set_interner(&mut self, interner: Arc<dyn Interner>) {
+        self.interner = interner;
+    }
+
+    pub fn sink(&self) -> &Arc<Sink> {
+        &self.sink
+    }
+
+    /// Whether input other than whitespace is buffered but not yet tokenized.
+    pub fn has_pending(&self) -> bool {
+        !self.input.is_blank()
+    }
+
+    /// Tokenizes as much of the buffered input as possible. Text that runs
+    /// up to the end of the buffer is held back until a `<` or `>` arrives or
+    /// `end` is called, so chunks may be split anywhere.
     pub fn feed(&mut self, input: CharQueue) {
         if input.is_empty() {
             return;
         }
 
-        self.run(input);
+        let retry = !self.held || input.any(|c| c == '<' || c == '>');
+        self.input.append(input);
+        if retry {
+            self.run();
+        }
     }
 
-    fn run(&mut self, mut input: CharQueue) {
-        let mut input = ParseQueue::new(&mut input);
-        loop {
-            match self.step(&mut input) {
-                ProcessResult::Continue => (),
-                ProcessResult::Suspend => break,
-            }
+    /// Marks the end of input and flushes any held back token.
+    pub fn end(&mut self) {
+        self.at_end = true;
+        if !self.input.is_empty() {
+            self.run();
         }
     }
 
+    fn run(&mut self) {
+        self.held = false;
+        let mut queue = std::mem::take(&mut self.input);
+        let mut input = ParseQueue::new(&mut queue);
+        while let ProcessResult::Continue = self.step(&mut input) {}
+        self.input = queue;
+    }
+
     fn step(&mut self, input: &mut ParseQueue) -> ProcessResult {
         let token = match &self.state {
-            State::Base => parse_token().parse(input),
-            State::Special(sp) => {
-                let token = parse_special(sp.as_ref()).parse(input);
-                if let Ok(Token::Tag(_)) = token {
-                    self.state = State::Base;
-                }
-                token
-            }
+            State::Base => parse_token(self.interner.as_ref()).parse(input),
+            State::Special(sp) => parse_special(sp.as_ref(), self.interner.as_ref()).parse(input),
         };
 
         match token {
+            // text may go on in the next chunk, anything else ends with `>`
+            Ok(Token::Text(_)) if !self.at_end && input.peek().is_none() => {
+                input.revert();
+                self.held = true;
+                ProcessResult::Suspend
+            }
             Ok(token) => {
+                if let (State::Special(_), Token::Tag(_)) = (&self.state, &token) {
+                    self.state = State::Base;
+                }
+
+                let len = input.byte_position();
                 input.update();
-                self.emit_token(token)
+                let span = Span::new(self.offset, self.offset + len);
+                self.offset += len;
+                self.emit_token(token, span)
             }
-            Err(err) => self.handle_err(err),
+            Err(err) => self.handle_err(err, input),
         }
     }
 
-    fn emit_token(&mut self, token: Token) -> ProcessResult {
-        match self.sink.process_token(token) {
+    fn emit_token(&mut self, token: Token, span: Span) -> ProcessResult {
+        match self.sink.process_spanned(token, span) {
             TokenSinkResult::Continue => ProcessResult::Continue,
             TokenSinkResult::Special(sp) => {
                 self.state = State::Special(sp);
@@ -86,10 +132,22 @@ impl Tokenizer {
         }
     }
 
-    fn handle_err(&mut self, err: PError) -> ProcessResult {
+    fn handle_err(&mut self, err: PError, input: &mut ParseQueue) -> ProcessResult {
         match err {
-            PError::InvalidChar(c) => self.emit_token(Token::InvalidChar(c)),
-            PError::EndOfInput => self.emit_token(Token::EndOfInput),
+            // the char the failed token starts with is skipped, so the input
+            // after it is still tokenized
+            PError::InvalidChar(c) => {
+                input.dequeue();
+                let len = input.byte_position();
+                input.update();
+                let span = Span::new(self.offset, self.offset + len);
+                self.offset += len;
+                self.emit_token(Token::InvalidChar(c), span)
+            }
+            PError::EndOfInput => {
+                let span = Span::new(self.offset, self.offset);
+                self.emit_token(Token::EndOfInput, span)
+            }
         }
     }
 }
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use crate::{
+    dom::parser::{
+        sink::{SpannedToken, TokenQueue},
+        tokenizer::Tokenizer,
+    },
+    utils::CharQueue,
+};
+
+/// Tokenizes `input` without building a tree.
+pub fn tokens(input: &str) -> Tokens {
+    let mut tokens = Tokens::new();
+    tokens.feed(input);
+    tokens.end();
+    tokens
+}
+
+/// Iterator over the tokens of an HTML input. Input can be fed in chunks;
+/// the tokens available so far are yielded in between.
+pub struct Tokens {
+    tokenizer: Tokenizer<TokenQueue>,
+}
+
+impl Tokens {
+    pub fn new() -> Self {
+        Tokens {
+            tokenizer: Tokenizer::new(Arc::new(TokenQueue::new())),
+        }
+    }
+
+    pub fn feed(&mut self, input: &str) {
+        self.tokenizer.feed(CharQueue::from_str(input));
+    }
+
+    pub fn end(&mut self) {
+        self.tokenizer.end();
+    }
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = SpannedToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokenizer.sink().pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // debug form of each token with its span, comparable across feeds
+    fn describe(tokens: impl Iterator<Item = SpannedToken>) -> Vec<(String, usize, usize)> {
+        tokens
+            .map(|t| (format!("{:?}", t.token), t.span.start, t.span.end))
+            .collect()
+    }
+
+    #[test]
+    fn chunked_feed_matches_single_feed() {
+        let input =
+            "<div class=\"a b\"><p>Héllo <b>wörld</b></p><script>if (a < b) {}</script>tail</div>";
+        let expected = describe(tokens(input));
+
+        for size in [1, 2, 3, 7] {
+            let chars = input.chars().collect::<Vec<_>>();
+            let mut stream = Tokens::new();
+            let mut found = Vec::new();
+            for chunk in chars.chunks(size) {
+                stream.feed(&chunk.iter().collect::<String>());
+                found.extend(describe(stream.by_ref()));
+            }
+            stream.end();
+            found.extend(describe(stream));
+            assert_eq!(found, expected, "chunks of {size} chars");
+        }
+    }
+
+    #[test]
+    fn control_chars_do_not_stall_the_stream() {
+        let mut stream = Tokens::new();
+        stream.feed("\u{1}<b>");
+        stream.feed("x");
+        stream.end();
+        assert_eq!(
+            describe(stream),
+            [
+                ("Text(\"\\u{1}\")".to_string(), 0, 1),
+                (
+                    "Tag(Tag { kind: StartTag, name: \"b\", attrs: [], values: \"\" })".to_string(),
+                    1,
+                    4
+                ),
+                ("Text(\"x\")".to_string(), 4, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_text_is_held_until_a_tag() {
+        let mut stream = Tokens::new();
+        for _ in 0..20_000 {
+            stream.feed("a");
+            assert!(stream.next().is_none());
+        }
+        stream.feed("<b>");
+        let found = describe(stream.by_ref());
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].1, found[0].2), (0, 20_000));
+        assert_eq!((found[1].1, found[1].2), (20_000, 20_003));
+    }
+
+    #[test]
+    fn spans_cover_the_input() {
+        let input = "<p>a<br/>ü</p>";
+        let spans = tokens(input).map(|t| t.span).collect::<Vec<_>>();
+        assert_eq!(spans.first().unwrap().start, 0);
+        assert_eq!(spans.last().unwrap().end, input.len());
+        assert!(spans.windows(2).all(|w| w[0].end == w[1].start));
+    }
+}
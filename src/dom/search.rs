@@ -0,0 +1,226 @@
+use crate::{
+    dom::{navigation::Ancestors, Document, DomNode},
+    utils::{LazyStr, Normalization},
+};
+
+/// An occurrence of a searched text.
+#[derive(Debug, Clone, Copy)]
+pub struct TextMatch<'a> {
+    /// Byte range in the document text, see `Document::lazy_base`.
+    pub start: usize,
+    pub end: usize,
+    /// Deepest node whose text contains the whole occurrence.
+    pub node: DomNode<'a>,
+}
+
+impl<'a> TextMatch<'a> {
+    pub fn text(&self) -> LazyStr {
+        LazyStr::new(
+            self.node.document().lazy_base().clone(),
+            self.start,
+            self.end,
+        )
+    }
+
+    /// Ancestors of `node`, starting with its parent.
+    pub fn ancestors(&self) -> Ancestors<'a> {
+        self.node.ancestors()
+    }
+}
+
+/// A window of text around a hit.
+#[derive(Debug, Clone)]
+pub struct Snippet<'a> {
+    pub text: String,
+    /// Byte range of the hit within `text`.
+    pub hit: (usize, usize),
+    /// Element enclosing the whole hit.
+    pub element: DomNode<'a>,
+    pub text_match: TextMatch<'a>,
+}
+
+impl Document {
+    /// Finds every occurrence of `needle` in document order, using the
+    /// suffix array of the document text. Text of neighbouring nodes is
+    /// separated by a space, so `Price: <b>10</b>` matches `Price: 10`.
+    pub fn find_text(&self, needle: &str) -> Vec<TextMatch<'_>> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        let mut starts = self.lazy_base().find_all(needle);
+        starts.sort_unstable();
+        self.text_matches(
+            starts
+                .into_iter()
+                .map(|start| (start, start + needle.len())),
+        )
+    }
+
+    /// Like `find_text`, comparing the text and `needle` after normalizing
+    /// both, e.g. case insensitively. Ranges are in the original text.
+    pub fn find_text_normalized(
+        &self,
+        needle: &str,
+        normalization: Normalization,
+    ) -> Vec<TextMatch<'_>> {
+        let ranges = self.lazy_base().find_normalized(needle, normalization);
+        self.text_matches(ranges)
+    }
+
+    fn text_matches<I>(&self, ranges: I) -> Vec<TextMatch<'_>>
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        ranges
+            .into_iter()
+            .filter_map(|(start, end)| {
+                let node = self.deepest_containing(start, end)?;
+                Some(TextMatch { start, end, node })
+            })
+            .collect()
+    }
+
+    pub(crate) fn deepest_containing(&self, start: usize, end: usize) -> Option<DomNode<'_>> {
+        let contains = |node: &DomNode| {
+            let (node_start, node_end) = self.span(node.node_id());
+            node_start <= start && end <= node_end
+        };
+
+        let mut node = Some(self.root()).filter(contains)?;
+        while let Some(child) = node.child_nodes().find(contains) {
+            node = child;
+        }
+        Some(node)
+    }
+
+    /// Every occurrence of `needle` with up to `context_chars` characters of
+    /// text on each side, without cutting words in half.
+    pub fn snippets(&self, needle: &str, context_chars: usize) -> Vec<Snippet<'_>> {
+        let (offset, _) = self.span(self.root);
+        let text = self.root().text();
+
+        self.find_text(needle)
+            .into_iter()
+            .map(|text_match| {
+                let hit_start = text_match.start - offset;
+                let hit_end = text_match.end - offset;
+                let start = context_start(text, hit_start, context_chars);
+                let end = context_end(text, hit_end, context_chars);
+
+                let element = match text_match.node.is_element() {
+                    true => text_match.node,
+                    false => text_match.ancestors().next().unwrap_or(text_match.node),
+                };
+                Snippet {
+                    text: text[start..end].to_string(),
+                    hit: (hit_start - start, hit_end - start),
+                    element,
+                    text_match,
+                }
+            })
+            .collect()
+    }
+}
+
+// start of the context before `hit`, moved to the next word if it falls
+// inside one
+fn context_start(text: &str, hit: usize, chars: usize) -> usize {
+    let before = &text[..hit];
+    if chars == 0 {
+        return hit;
+    }
+
+    let mut start = before
+        .char_indices()
+        .rev()
+        .nth(chars - 1)
+        .map_or(0, |(idx, _)| idx);
+    if before[..start].ends_with(|c: char| !c.is_whitespace()) {
+        start = match before[start..].find(char::is_whitespace) {
+            Some(idx) => start + idx,
+            None => hit,
+        };
+    }
+    hit - before[start..].trim_start().len()
+}
+
+// end of the context after `hit`, moved back to the previous word if it
+// falls inside one
+fn context_end(text: &str, hit: usize, chars: usize) -> usize {
+    let after = &text[hit..];
+    let mut end = after
+        .char_indices()
+        .nth(chars)
+        .map_or(after.len(), |(idx, _)| idx);
+
+    if after[end..].starts_with(|c: char| !c.is_whitespace()) {
+        end = after[..end].rfind(char::is_whitespace).unwrap_or(0);
+    }
+    hit + after[..end].trim_end().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom::DomBuilder, utils::Normalization};
+
+    #[test]
+    fn finds_text_across_nodes() {
+        let document =
+            DomBuilder::parse("<div><p>Price: <b>10</b> EUR</p><p>Price: 12</p></div>").unwrap();
+
+        let matches = document.find_text("Price: 1");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].node.tag().as_ref(), "p");
+        assert_eq!(matches[0].text().to_string(), "Price: 1");
+        assert!(matches[0].start < matches[1].start);
+
+        let matches = document.find_text("10");
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].node.is_text());
+        assert_eq!(matches[0].ancestors().next().unwrap().tag().as_ref(), "b");
+
+        assert!(document.find_text("").is_empty());
+        assert!(document.find_text("Price: 13").is_empty());
+    }
+
+    #[test]
+    fn finds_non_ascii_text() {
+        let document = DomBuilder::parse("<ul><li>crème brûlée</li><li>Crème</li></ul>").unwrap();
+        assert_eq!(document.find_text("crème").len(), 1);
+        assert_eq!(document.find_text("è").len(), 2);
+
+        let matches = document.find_text_normalized("creme", Normalization::LOOSE);
+        let texts = matches
+            .iter()
+            .map(|hit| hit.text().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["crème", "Crème"]);
+    }
+
+    #[test]
+    fn snippets_keep_whole_words() {
+        let document =
+            DomBuilder::parse("<p>The quick brown fox jumps over the lazy dog</p>").unwrap();
+        let snippets = document.snippets("fox", 8);
+        assert_eq!(snippets.len(), 1);
+        let snippet = &snippets[0];
+        assert_eq!(snippet.text, "brown fox jumps");
+        assert_eq!(&snippet.text[snippet.hit.0..snippet.hit.1], "fox");
+        assert_eq!(snippet.element.tag().as_ref(), "p");
+    }
+
+    #[test]
+    fn finds_normalized_text_after_mutations() {
+        let mut document = DomBuilder::parse("<div><p>one</p></div>").unwrap();
+        let p = document.root().children().next().unwrap().node_id();
+        document.set_text(p, "Über").unwrap();
+
+        let matches = document.find_text_normalized("uber", Normalization::LOOSE);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].ancestors().next().unwrap().node_id(), p);
+        assert!(document
+            .find_text_normalized("one", Normalization::LOOSE)
+            .is_empty());
+    }
+}
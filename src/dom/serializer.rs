@@ -0,0 +1,527 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use crate::dom::{node::AttributeRef, Document, DomNode};
+
+/// Layout of the serialized markup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// The tree as it is.
+    #[default]
+    Plain,
+    /// One block per line, nested blocks indented by `indent` spaces. Content
+    /// with text or inline elements stays on one line.
+    Pretty { indent: usize },
+    /// Whitespace runs collapsed. Comments are never kept in the tree, so
+    /// there are none to remove. End tags are all written, even optional
+    /// ones, as the parser doesn't infer omitted end tags.
+    Minified,
+}
+
+/// Writes nodes as HTML markup.
+///
+/// Text is escaped as it is stored, an `&` always becomes `&amp;`. The parser
+/// trims text nodes, so a space is written between a text node and an inline
+/// sibling, like `inner_text` separates them. Otherwise whitespace inside
+/// `pre`, `textarea` and raw-text elements is never touched.
+pub struct HtmlSerializer<W: Write> {
+    writer: W,
+    format: Format,
+    // number of open whitespace preserving elements
+    preserve: usize,
+    // sorted ranges of the document text wrapped in `<mark>`
+    highlights: Vec<(usize, usize)>,
+}
+
+enum Step<'a> {
+    // a new line, indented to the depth
+    Line(usize),
+    // whitespace between inline siblings
+    Space,
+    Enter {
+        node: DomNode<'a>,
+        depth: usize,
+        // whether the content may be laid out as blocks
+        layout: bool,
+    },
+    Leave {
+        node: DomNode<'a>,
+        depth: usize,
+        block: bool,
+    },
+}
+
+impl<W: Write> HtmlSerializer<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_format(writer, Format::Plain)
+    }
+
+    pub fn with_format(writer: W, format: Format) -> Self {
+        HtmlSerializer {
+            writer,
+            format,
+            preserve: 0,
+            highlights: Vec::new(),
+        }
+    }
+
+    /// Wraps the given byte ranges of the document text, as found by
+    /// `Document::find_text`, in `<mark>`. A range spanning several text
+    /// nodes is marked in each of them.
+    pub fn highlight<I>(&mut self, ranges: I)
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        self.highlights.extend(ranges);
+        self.highlights.sort_unstable();
+
+        // overlapping ranges become one mark
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(self.highlights.len());
+        for (start, end) in self.highlights.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.highlights = merged;
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub(crate) fn void_tags() -> &'static HashSet<&'static str> {
+        static VOID_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+        VOID_TAGS.get_or_init(|| {
+            HashSet::from([
+                "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img",
+                "input", "keygen", "link", "meta", "param", "source", "track", "wbr",
+            ])
+        })
+    }
+
+    pub(crate) fn raw_text_tags() -> &'static HashSet<&'static str> {
+        static RAW_TEXT_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+        RAW_TEXT_TAGS.get_or_init(|| {
+            HashSet::from([
+                "iframe",
+                "noembed",
+                "noframes",
+                "noscript",
+                "plaintext",
+                "script",
+                "style",
+                "xmp",
+            ])
+        })
+    }
+
+    pub(crate) fn preserve_tags() -> &'static HashSet<&'static str> {
+        static PRESERVE_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+        PRESERVE_TAGS.get_or_init(|| {
+            let mut tags = HashSet::from(["listing", "pre", "textarea"]);
+            tags.extend(Self::raw_text_tags());
+            tags
+        })
+    }
+
+    pub(crate) fn inline_tags() -> &'static HashSet<&'static str> {
+        static INLINE_TAGS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+        INLINE_TAGS.get_or_init(|| {
+            HashSet::from([
+                "a", "abbr", "b", "bdi", "bdo", "br", "button", "cite", "code", "data", "del",
+                "dfn", "em", "i", "img", "input", "ins", "kbd", "label", "mark", "q", "s", "samp",
+                "select", "small", "span", "strong", "sub", "sup", "textarea", "time", "u", "var",
+                "wbr",
+            ])
+        })
+    }
+
+    /// Writes the node and its subtree.
+    pub fn serialize(&mut self, node: DomNode) -> io::Result<()> {
+        self.preserve = node
+            .ancestors()
+            .filter(|node| Self::preserve_tags().contains(node.tag().as_ref()))
+            .count();
+        let mut stack = vec![Step::Enter {
+            node,
+            depth: 0,
+            layout: self.preserve == 0,
+        }];
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Line(depth) => self.write_line(depth)?,
+                Step::Space => self.writer.write_all(b" ")?,
+                Step::Enter { node, .. } if node.is_text() => self.write_text(node)?,
+                Step::Enter {
+                    node,
+                    depth,
+                    layout,
+                } => {
+                    self.write_start(node)?;
+                    let preserve = Self::preserve_tags().contains(node.tag().as_ref());
+                    if preserve {
+                        self.preserve += 1;
+                    }
+
+                    let block = layout && !preserve && self.is_block_content(node);
+                    stack.push(Step::Leave { node, depth, block });
+
+                    let mut child = node.last_child_node();
+                    while let Some(node) = child {
+                        stack.push(Step::Enter {
+                            node,
+                            depth: depth + 1,
+                            layout: block,
+                        });
+                        if block {
+                            stack.push(Step::Line(depth + 1));
+                        }
+                        child = node.prev_node();
+                        if child.is_some_and(|prev| !block && Self::separated(prev, node)) {
+                            stack.push(Step::Space);
+                        }
+                    }
+                }
+                Step::Leave { node, depth, block } => {
+                    if Self::preserve_tags().contains(node.tag().as_ref()) {
+                        self.preserve -= 1;
+                    }
+                    if block {
+                        self.write_line(depth)?;
+                    }
+                    self.write_end(node)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the children of the node.
+    pub fn serialize_children(&mut self, node: DomNode) -> io::Result<()> {
+        let block = self.is_block_content(node);
+        for (idx, child) in node.child_nodes().enumerate() {
+            if block && idx > 0 {
+                self.write_line(0)?;
+            }
+            if child
+                .prev_node()
+                .is_some_and(|prev| !block && Self::separated(prev, child))
+            {
+                self.writer.write_all(b" ")?;
+            }
+            self.serialize(child)?;
+        }
+        Ok(())
+    }
+
+    // whether whitespace trimmed by the parser has to be put back between
+    // two siblings, which is when both are text or inline elements
+    fn separated(prev: DomNode, next: DomNode) -> bool {
+        let inline =
+            |node: DomNode| node.is_text() || Self::inline_tags().contains(node.tag().as_ref());
+        let raw = next
+            .parent()
+            .is_some_and(|parent| Self::raw_text_tags().contains(parent.tag().as_ref()));
+        let spaced = (prev.is_text() && prev.text().ends_with(char::is_whitespace))
+            || (next.is_text() && next.text().starts_with(char::is_whitespace));
+
+        inline(prev) && inline(next) && !raw && !spaced
+    }
+
+    // when pretty printing, children which are all block elements go on
+    // their own lines, anything else could render differently
+    fn is_block_content(&self, node: DomNode) -> bool {
+        matches!(self.format, Format::Pretty { .. })
+            && node.first_child_node().is_some()
+            && node.child_nodes().all(|child| {
+                child.is_element() && !Self::inline_tags().contains(child.tag().as_ref())
+            })
+    }
+
+    fn write_line(&mut self, depth: usize) -> io::Result<()> {
+        let indent = match self.format {
+            Format::Pretty { indent } => indent,
+            _ => 0,
+        };
+        write!(self.writer, "\n{:1$}", "", indent * depth)
+    }
+
+    fn write_start(&mut self, node: DomNode) -> io::Result<()> {
+        write!(self.writer, "<{}", node.tag())?;
+        for AttributeRef { name, value } in node.attributes() {
+            match value {
+                None => write!(self.writer, " {}", name)?,
+                Some(value) => {
+                    write!(self.writer, " {}=\"", name)?;
+                    self.write_escaped(value, true)?;
+                    write!(self.writer, "\"")?;
+                }
+            }
+        }
+        write!(self.writer, ">")
+    }
+
+    // void elements have no end tag, the parser may still have given them
+    // children, which then simply follow the start tag
+    fn write_end(&mut self, node: DomNode) -> io::Result<()> {
+        match Self::void_tags().contains(node.tag().as_ref()) {
+            true => Ok(()),
+            false => write!(self.writer, "</{}>", node.tag()),
+        }
+    }
+
+    fn write_text(&mut self, node: DomNode) -> io::Result<()> {
+        let text = node.text();
+        let raw = node
+            .parent()
+            .is_some_and(|parent| Self::raw_text_tags().contains(parent.tag().as_ref()));
+        if raw {
+            return self.writer.write_all(text.as_bytes());
+        }
+
+        let (start, end) = node.document().span(node.node_id());
+        let first = self
+            .highlights
+            .partition_point(|(_, hl_end)| *hl_end <= start);
+        let mut last = 0;
+        for idx in first..self.highlights.len() {
+            let (hl_start, hl_end) = self.highlights[idx];
+            if hl_start >= end {
+                break;
+            }
+
+            let hl_start = hl_start.max(start) - start;
+            let hl_end = hl_end.min(end) - start;
+            self.write_text_part(&text[last..hl_start])?;
+            write!(self.writer, "<mark>")?;
+            self.write_text_part(&text[hl_start..hl_end])?;
+            write!(self.writer, "</mark>")?;
+            last = hl_end;
+        }
+        self.write_text_part(&text[last..])
+    }
+
+    fn write_text_part(&mut self, text: &str) -> io::Result<()> {
+        match self.format == Format::Minified && self.preserve == 0 {
+            true => self.write_escaped(&collapse_whitespace(text), false),
+            false => self.write_escaped(text, false),
+        }
+    }
+
+    fn write_escaped(&mut self, text: &str, attribute: bool) -> io::Result<()> {
+        let mut last = 0;
+        for (idx, c) in text.char_indices() {
+            let escaped = match c {
+                '&' => "&amp;",
+                '\u{a0}' => "&nbsp;",
+                '"' if attribute => "&quot;",
+                '<' if !attribute => "&lt;",
+                '>' if !attribute => "&gt;",
+                _ => continue,
+            };
+            self.writer.write_all(&text.as_bytes()[last..idx])?;
+            self.writer.write_all(escaped.as_bytes())?;
+            last = idx + c.len_utf8();
+        }
+        self.writer.write_all(&text.as_bytes()[last..])
+    }
+}
+
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        match c.is_ascii_whitespace() {
+            true => space = true,
+            false => {
+                if space {
+                    out.push(' ');
+                    space = false;
+                }
+                out.push(c);
+            }
+        }
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+fn render<F>(write: F) -> String
+where
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    let mut out = Vec::new();
+    write(&mut out).expect("writing to a Vec can't fail");
+    String::from_utf8(out).expect("serialized markup is valid UTF-8")
+}
+
+impl DomNode<'_> {
+    /// Markup of the node, itself included.
+    pub fn outer_html(&self) -> String {
+        render(|out| self.write_html(out))
+    }
+
+    /// Markup of the children of the node.
+    pub fn inner_html(&self) -> String {
+        render(|out| self.write_inner_html(out))
+    }
+
+    pub fn write_html<W: Write>(&self, writer: W) -> io::Result<()> {
+        HtmlSerializer::new(writer).serialize(*self)
+    }
+
+    /// Markup of the node with every occurrence of `needle` in `<mark>`.
+    pub fn highlighted_html(&self, needle: &str) -> String {
+        let hits = self
+            .document()
+            .find_text(needle)
+            .into_iter()
+            .map(|hit| (hit.start, hit.end));
+
+        render(|out| {
+            let mut serializer = HtmlSerializer::new(out);
+            serializer.highlight(hits);
+            serializer.serialize(*self)
+        })
+    }
+
+    pub fn write_inner_html<W: Write>(&self, writer: W) -> io::Result<()> {
+        HtmlSerializer::new(writer).serialize_children(*self)
+    }
+}
+
+impl Document {
+    pub fn html(&self) -> String {
+        self.root().outer_html()
+    }
+
+    /// Markup with nested blocks indented by `indent` spaces.
+    pub fn to_pretty_html(&self, indent: usize) -> String {
+        render(|out| {
+            HtmlSerializer::with_format(out, Format::Pretty { indent }).serialize(self.root())
+        })
+    }
+
+    pub fn to_minified_html(&self) -> String {
+        render(|out| HtmlSerializer::with_format(out, Format::Minified).serialize(self.root()))
+    }
+
+    pub fn write_html<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.root().write_html(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::{node::AttributeValue, Document, DomBuilder};
+
+    fn parse(input: &str) -> Document {
+        let builder = DomBuilder::new();
+        builder.feed(input);
+        builder.finalize().unwrap()
+    }
+
+    #[test]
+    fn keeps_whitespace_between_inline_siblings() {
+        let input = "<div><p>Hello <b>world</b> again</p><p><i>a</i><i>b</i>.</p></div>";
+        let document = parse(input);
+        let text = document.root().inner_text();
+        assert_eq!(text, "Hello world again\n\na b .");
+
+        assert_eq!(
+            document.html(),
+            "<div><p>Hello <b>world</b> again</p><p><i>a</i> <i>b</i> .</p></div>"
+        );
+        for html in [
+            document.html(),
+            document.to_pretty_html(2),
+            document.to_minified_html(),
+        ] {
+            assert_eq!(parse(&html).root().inner_text(), text, "{}", html);
+        }
+
+        let p = document.root().children().next().unwrap();
+        assert_eq!(p.inner_html(), "Hello <b>world</b> again");
+    }
+
+    #[test]
+    fn pretty_puts_blocks_on_lines() {
+        let document = parse("<div><p>Hello <b>world</b></p><ul><li>a</li></ul></div>");
+        assert_eq!(
+            document.to_pretty_html(2),
+            "<div>\n  <p>Hello <b>world</b></p>\n  <ul>\n    <li>a</li>\n  </ul>\n</div>"
+        );
+    }
+
+    #[test]
+    fn minified_output_parses_back() {
+        for input in [
+            "<div><p>a</p><p>b</p></div>",
+            "<ul><li>1</li><li>2</li></ul>",
+            "<dl><dt>a</dt><dd>b</dd><dt>c</dt><dd>d</dd></dl>",
+            "<select><option>a</option><option>b</option></select>",
+            "<html><head><title>t</title></head><body><p>x <b>y</b></p><br/></body></html>",
+        ] {
+            let document = parse(input);
+            let minified = document.to_minified_html();
+            assert_eq!(parse(&minified).html(), document.html(), "{}", minified);
+        }
+
+        let document = parse("<ul><li>a   b</li><li>c</li></ul>");
+        assert_eq!(
+            document.to_minified_html(),
+            "<ul><li>a b</li><li>c</li></ul>"
+        );
+    }
+
+    #[test]
+    fn always_escapes_ampersands() {
+        let mut document = parse("<p>a &amp; b &lt; c</p>");
+        assert_eq!(document.html(), "<p>a &amp;amp; b &amp;lt; c</p>");
+
+        let root = document.root().node_id();
+        let value = AttributeValue::Literal(r#"say "hi" & <go>"#.to_string());
+        document.set_attr(root, "title", value).unwrap();
+        let text = document.nodes().first_child(root).unwrap();
+        document.set_text(text, "1 < 2 && 3 > 2").unwrap();
+        assert_eq!(
+            document.html(),
+            r#"<p title="say &quot;hi&quot; &amp; <go>">1 &lt; 2 &amp;&amp; 3 &gt; 2</p>"#
+        );
+    }
+
+    #[test]
+    fn raw_text_and_void_elements() {
+        let document = parse("<div><br/><script>if (a && b) {}</script></div>");
+        assert_eq!(
+            document.html(),
+            "<div><br><script>if (a && b) {}</script></div>"
+        );
+    }
+
+    #[test]
+    fn highlights_hits_across_nodes() {
+        let document = parse("<div><p>Price: <b>10</b> EUR</p><p>Price: 12 or 10</p></div>");
+        assert_eq!(
+            document.root().highlighted_html("Price: 1"),
+            "<div><p><mark>Price:</mark> <b><mark>1</mark>0</b> EUR</p>\
+             <p><mark>Price: 1</mark>2 or 10</p></div>"
+        );
+        let p = document.root().children().next().unwrap();
+        assert_eq!(
+            p.highlighted_html("10"),
+            "<p>Price: <b><mark>10</mark></b> EUR</p>"
+        );
+    }
+}
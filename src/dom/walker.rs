@@ -0,0 +1,311 @@
+use crate::dom::{arena::NodeId, DomNode, NodeKind};
+
+/// Entering a node happens before its children are walked, leaving it
+/// after. Every entered node is left again.
+#[derive(Debug, Clone, Copy)]
+pub enum WalkEvent<'a> {
+    Enter(DomNode<'a>),
+    Leave(DomNode<'a>),
+}
+
+impl<'a> WalkEvent<'a> {
+    pub fn node(&self) -> DomNode<'a> {
+        match self {
+            WalkEvent::Enter(node) | WalkEvent::Leave(node) => *node,
+        }
+    }
+}
+
+/// Whether a `TreeWalker` reports a node, like the `NodeFilter` of the DOM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkFilter {
+    Accept,
+    /// Leave out the node but still walk its children.
+    Skip,
+    /// Leave out the node and its subtree.
+    Reject,
+}
+
+type Filter<'a> = Box<dyn Fn(DomNode<'a>) -> WalkFilter + 'a>;
+
+/// Walks the subtree of a node, text nodes included, reporting enter and
+/// leave events in document order.
+pub struct TreeWalker<'a> {
+    root: DomNode<'a>,
+    // next event before filtering
+    next: Option<WalkEvent<'a>>,
+    filter: Option<Filter<'a>>,
+    // open nodes left out by the filter, their leave events are dropped too
+    skipped: Vec<NodeId>,
+    // last reported enter event, target of `skip_subtree`
+    entered: Option<DomNode<'a>>,
+}
+
+impl<'a> TreeWalker<'a> {
+    pub fn new(root: DomNode<'a>) -> Self {
+        TreeWalker {
+            root,
+            next: Some(WalkEvent::Enter(root)),
+            filter: None,
+            skipped: Vec::new(),
+            entered: None,
+        }
+    }
+
+    /// Decides per node whether it's reported, the root included.
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(DomNode<'a>) -> WalkFilter + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Doesn't walk the children of the node just entered, its leave event
+    /// comes next.
+    pub fn skip_subtree(&mut self) {
+        if let Some(node) = self.entered.take() {
+            self.next = Some(WalkEvent::Leave(node));
+        }
+    }
+
+    /// Nodes in pre-order, parents before their children.
+    pub fn pre_order(self) -> impl Iterator<Item = DomNode<'a>> {
+        self.filter_map(|event| match event {
+            WalkEvent::Enter(node) => Some(node),
+            WalkEvent::Leave(_) => None,
+        })
+    }
+
+    /// Nodes in post-order, children before their parents.
+    pub fn post_order(self) -> impl Iterator<Item = DomNode<'a>> {
+        self.filter_map(|event| match event {
+            WalkEvent::Enter(_) => None,
+            WalkEvent::Leave(node) => Some(node),
+        })
+    }
+
+    // event following `event`, ignoring the filter
+    fn advance(&self, event: WalkEvent<'a>) -> Option<WalkEvent<'a>> {
+        match event {
+            WalkEvent::Enter(node) => match node.first_child_node() {
+                Some(child) => Some(WalkEvent::Enter(child)),
+                None => Some(WalkEvent::Leave(node)),
+            },
+            WalkEvent::Leave(node) => self.after(node),
+        }
+    }
+
+    // event following the whole subtree of `node`
+    fn after(&self, node: DomNode<'a>) -> Option<WalkEvent<'a>> {
+        if node.node_id() == self.root.node_id() {
+            return None;
+        }
+        match node.next_node() {
+            Some(sibling) => Some(WalkEvent::Enter(sibling)),
+            None => node.parent().map(WalkEvent::Leave),
+        }
+    }
+
+    fn decide(&self, node: DomNode<'a>) -> WalkFilter {
+        self.filter
+            .as_ref()
+            .map_or(WalkFilter::Accept, |filter| filter(node))
+    }
+}
+
+impl<'a> Iterator for TreeWalker<'a> {
+    type Item = WalkEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entered = None;
+        loop {
+            let event = self.next?;
+            match event {
+                WalkEvent::Enter(node) => match self.decide(node) {
+                    WalkFilter::Accept => {
+                        self.next = self.advance(event);
+                        self.entered = Some(node);
+                        return Some(event);
+                    }
+                    WalkFilter::Skip => {
+                        self.skipped.push(node.node_id());
+                        self.next = self.advance(event);
+                    }
+                    WalkFilter::Reject => self.next = self.after(node),
+                },
+                WalkEvent::Leave(node) => {
+                    self.next = self.advance(event);
+                    if self.skipped.last() == Some(&node.node_id()) {
+                        self.skipped.pop();
+                    } else {
+                        return Some(event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Callbacks for `DomNode::accept`, each defaulting to doing nothing.
+pub trait Visitor<'a> {
+    /// Called before the children of `node`, `false` skips them.
+    fn visit_element(&mut self, node: DomNode<'a>) -> bool {
+        let _ = node;
+        true
+    }
+
+    /// Called after the children of `node`.
+    fn leave_element(&mut self, node: DomNode<'a>) {
+        let _ = node;
+    }
+
+    fn visit_text(&mut self, node: DomNode<'a>) {
+        let _ = node;
+    }
+}
+
+impl<'a> DomNode<'a> {
+    /// Walks the subtree of the node, the node included.
+    pub fn walk(&self) -> TreeWalker<'a> {
+        TreeWalker::new(*self)
+    }
+
+    /// Drives `visitor` over the subtree of the node, the node included.
+    pub fn accept<V: Visitor<'a>>(&self, visitor: &mut V) {
+        let mut walker = self.walk();
+        while let Some(event) = walker.next() {
+            match (event, event.node().kind()) {
+                (WalkEvent::Enter(node), NodeKind::Element) => {
+                    if !visitor.visit_element(node) {
+                        walker.skip_subtree();
+                    }
+                }
+                (WalkEvent::Enter(node), NodeKind::Text) => visitor.visit_text(node),
+                (WalkEvent::Leave(node), NodeKind::Element) => visitor.leave_element(node),
+                (WalkEvent::Leave(_), NodeKind::Text) => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{Document, DomBuilder};
+
+    fn parse() -> Document {
+        DomBuilder::parse("<div><p>a <b>b</b></p><ul><li>c</li><li>d</li></ul></div>").unwrap()
+    }
+
+    // `<tag` and `tag>` per event, text nodes as their text
+    fn describe<'a>(events: impl Iterator<Item = WalkEvent<'a>>) -> String {
+        events
+            .map(|event| match (event, event.node().kind()) {
+                (WalkEvent::Enter(node), NodeKind::Element) => format!("<{}", node.tag()),
+                (WalkEvent::Leave(node), NodeKind::Element) => format!("{}>", node.tag()),
+                (WalkEvent::Enter(node), NodeKind::Text) => format!("'{}", node.text()),
+                (WalkEvent::Leave(node), NodeKind::Text) => format!("{}'", node.text()),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn events_are_balanced() {
+        let document = parse();
+        assert_eq!(
+            describe(document.root().walk()),
+            "<div <p 'a a' <b 'b b' b> p> <ul <li 'c c' li> <li 'd d' li> ul> div>"
+        );
+
+        let p = document.root().children().next().unwrap();
+        assert_eq!(describe(p.walk()), "<p 'a a' <b 'b b' b> p>");
+        let tags = |nodes: Vec<DomNode>| {
+            nodes
+                .iter()
+                .filter(|node| node.is_element())
+                .map(|node| node.tag().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tags(p.walk().pre_order().collect()), ["p", "b"]);
+        assert_eq!(tags(p.walk().post_order().collect()), ["b", "p"]);
+    }
+
+    #[test]
+    fn skip_subtree_still_leaves() {
+        let document = parse();
+        let mut walker = document.root().walk();
+        let mut events = Vec::new();
+        while let Some(event) = walker.next() {
+            events.push(event);
+            if let WalkEvent::Enter(node) = event {
+                if node.tag().as_ref() == "p" || node.tag().as_ref() == "li" {
+                    walker.skip_subtree();
+                }
+            }
+        }
+        assert_eq!(
+            describe(events.into_iter()),
+            "<div <p p> <ul <li li> <li li> ul> div>"
+        );
+    }
+
+    #[test]
+    fn skip_descends_and_reject_prunes() {
+        let document = parse();
+        let skip_ul = document
+            .root()
+            .walk()
+            .with_filter(|node| match node.tag().as_ref() {
+                "ul" | "#text" => WalkFilter::Skip,
+                _ => WalkFilter::Accept,
+            });
+        assert_eq!(describe(skip_ul), "<div <p <b b> p> <li li> <li li> div>");
+
+        let reject_ul = document
+            .root()
+            .walk()
+            .with_filter(|node| match node.tag().as_ref() {
+                "ul" => WalkFilter::Reject,
+                _ => WalkFilter::Accept,
+            });
+        assert_eq!(describe(reject_ul), "<div <p 'a a' <b 'b b' b> p> div>");
+
+        let reject_root = document.root().walk().with_filter(|_| WalkFilter::Reject);
+        assert_eq!(reject_root.count(), 0);
+    }
+
+    #[derive(Default)]
+    struct Outline {
+        out: Vec<String>,
+    }
+
+    impl<'a> Visitor<'a> for Outline {
+        fn visit_element(&mut self, node: DomNode<'a>) -> bool {
+            self.out.push(format!("<{}", node.tag()));
+            node.tag().as_ref() != "ul"
+        }
+
+        fn leave_element(&mut self, node: DomNode<'a>) {
+            self.out.push(format!("{}>", node.tag()));
+        }
+
+        fn visit_text(&mut self, node: DomNode<'a>) {
+            self.out.push(node.text().to_string());
+        }
+    }
+
+    #[test]
+    fn visitor_gets_callbacks_per_kind() {
+        let document = parse();
+        let mut outline = Outline::default();
+        document.root().accept(&mut outline);
+        assert_eq!(outline.out.join(" "), "<div <p a <b b b> p> <ul ul> div>");
+
+        // the default methods walk everything without doing anything
+        struct Nothing;
+        impl Visitor<'_> for Nothing {}
+        document.root().accept(&mut Nothing);
+    }
+}
@@ -1,6 +1,6 @@
 use std::env;
 
-use xcraper::{dom::DomBuilder, xpath::XPathBuilder};
+use caper::{dom::DomBuilder, xpath::XPathBuilder};
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
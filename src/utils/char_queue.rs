@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct CharQueue {
     queue: VecDeque<char>,
 }
@@ -16,6 +16,18 @@ impl CharQueue {
         self.queue.is_empty()
     }
 
+    pub fn is_blank(&self) -> bool {
+        self.queue.iter().all(|c| c.is_whitespace())
+    }
+
+    pub fn any<F: Fn(char) -> bool>(&self, condition: F) -> bool {
+        self.queue.iter().any(|c| condition(*c))
+    }
+
+    pub fn append(&mut self, mut other: CharQueue) {
+        self.queue.append(&mut other.queue);
+    }
+
     pub fn peek(&self) -> Option<char> {
         self.queue.front().map(|c| c.clone())
     }
@@ -147,6 +159,16 @@ impl<'queue> ParseQueue<'queue> {
         result
     }
 
+    // byte length of the characters consumed since the last update
+    pub fn byte_position(&self) -> usize {
+        self.char_queue
+            .queue
+            .iter()
+            .take(self.position)
+            .map(|c| c.len_utf8())
+            .sum()
+    }
+
     pub fn update(&mut self) {
         self.char_queue.queue.drain(0..self.position);
         self.save_states.clear();
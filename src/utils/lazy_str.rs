@@ -1,64 +1,137 @@
-use std::{cell::RefCell, sync::Arc, usize};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    usize,
+};
 
-use super::substring_finder::StrFinder;
+use super::{
+    normalize::{Normalization, NormalizedIndex},
+    substring_finder::{StrFinder, SubstringStats},
+};
 
+/// Collects the text of a document while it's parsed, frozen into a
+/// `LazyBase` once complete.
+#[derive(Debug, Default)]
+pub struct LazyBaseBuilder {
+    base: String,
+}
+
+impl LazyBaseBuilder {
+    // returns the offset `str` was appended at
+    pub fn append(&mut self, str: &str) -> usize {
+        // Check if the last character is a space or whitespace
+        if let Some(last_char) = self.base.chars().last() {
+            if !matches!(last_char, ' ' | '\n' | '\r' | '\t') {
+                self.base.push(' ');
+            }
+        }
+
+        // Now append the new string
+        let start = self.base.len();
+        self.base.push_str(str);
+        start
+    }
+
+    pub fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.base.is_empty()
+    }
+
+    pub fn finalize(self) -> LazyBase {
+        LazyBase::init(self.base)
+    }
+}
+
+/// Immutable text of a document with its suffix array, safe to share
+/// between threads.
 #[derive(Debug)]
 pub struct LazyBase {
-    base: RefCell<String>,
-    str_finder: RefCell<StrFinder>,
+    base: String,
+    str_finder: StrFinder,
+    // built on the first search with each normalization
+    normalized: Mutex<Vec<Arc<NormalizedIndex>>>,
 }
 
 impl LazyBase {
     pub fn init(base: String) -> Self {
         LazyBase {
-            base: base.into(),
-            str_finder: StrFinder::default().into(),
+            str_finder: StrFinder::new(&base),
+            base,
+            normalized: Vec::new().into(),
         }
     }
 
-    pub fn append(&self, str: &str) {
-        let mut base_borrow = self.base.borrow_mut(); // Borrow mutably first
+    pub fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.base
+    }
 
-        // Check if the last character is a space or whitespace
-        if let Some(last_char) = base_borrow.chars().last() {
-            if !matches!(last_char, ' ' | '\n' | '\r' | '\t') {
-                base_borrow.push(' ');
-            }
-        }
+    pub fn find_all(&self, needle: &str) -> Vec<usize> {
+        self.str_finder.find_all(&self.base, needle)
+    }
 
-        // Now append the new string
-        base_borrow.push_str(str);
+    pub fn contains(&self, needle: &str) -> bool {
+        self.count(needle) > 0
     }
 
-    pub fn finalize(&self) {
-        self.str_finder.borrow_mut().change(&self.base.borrow());
+    /// Whether `needle` occurs within `start..end`, answered from the suffix
+    /// array unless the text has more occurrences than the range has bytes.
+    pub fn contains_in(&self, needle: &str, start: usize, end: usize) -> bool {
+        let hits = self.str_finder.occurrences(&self.base, needle);
+        if hits.len() > end - start {
+            return self.base[start..end].contains(needle);
+        }
+        hits.iter()
+            .any(|&pos| start <= pos && pos + needle.len() <= end)
     }
 
-    pub fn len(&self) -> usize {
-        self.base.borrow().len()
+    /// Byte ranges of the text matching `needle` once both are normalized.
+    pub fn find_normalized(
+        &self,
+        needle: &str,
+        normalization: Normalization,
+    ) -> Vec<(usize, usize)> {
+        let index = {
+            let mut indexes = self.normalized.lock().unwrap();
+            match indexes
+                .iter()
+                .find(|index| index.normalization() == normalization)
+            {
+                Some(index) => index.clone(),
+                None => {
+                    let index = Arc::new(NormalizedIndex::new(&self.base, normalization));
+                    indexes.push(index.clone());
+                    index
+                }
+            }
+        };
+        index.find_all(needle)
     }
 
-    pub fn find_all(&self, needle: &str) -> Vec<usize> {
-        self.str_finder
-            .borrow()
-            .find_all(&self.base.borrow(), needle)
+    pub fn count(&self, needle: &str) -> usize {
+        self.str_finder.count(&self.base, needle)
     }
 
-    pub fn contains(&self, needle: &str) -> bool {
-        !self
-            .str_finder
-            .borrow()
-            .find_all(&self.base.borrow(), needle)
-            .is_empty()
+    /// The longest text occurring at least twice, e.g. repeated boilerplate.
+    pub fn longest_repeated(&self) -> Option<&str> {
+        self.str_finder.longest_repeated(&self.base)
+    }
+
+    /// Statistics of the text, in bytes.
+    pub fn substring_stats(&self) -> SubstringStats {
+        self.str_finder.stats()
     }
 }
 
 impl Default for LazyBase {
     fn default() -> Self {
-        LazyBase {
-            base: String::new().into(),
-            str_finder: StrFinder::default().into(),
-        }
+        LazyBase::init(String::new())
     }
 }
 
@@ -70,16 +143,18 @@ pub struct LazyStr {
 }
 
 impl LazyStr {
+    pub fn new(base: Arc<LazyBase>, start: usize, end: usize) -> Self {
+        LazyStr { base, start, end }
+    }
+
     pub fn build(base: Arc<LazyBase>, sub: &str) -> Option<Self> {
         // Get raw pointers to the start of the string and the substring
         // let base_str = &base.as_ref().borrow().base;
-        let orig_ptr = base.as_ref().base.borrow().as_ptr();
+        let orig_ptr = base.as_ref().base.as_ptr();
         let sub_ptr = sub.as_ptr();
 
         // Ensure the substring is inside the string (the substring must be from the string)
-        if orig_ptr <= sub_ptr
-            && sub_ptr < unsafe { orig_ptr.add(base.as_ref().base.borrow().len()) }
-        {
+        if orig_ptr <= sub_ptr && sub_ptr < unsafe { orig_ptr.add(base.as_ref().base.len()) } {
             // Calculate the offset of the substring within the string
             let offset = sub_ptr as usize - orig_ptr as usize;
             Some(LazyStr {
@@ -106,14 +181,75 @@ impl LazyStr {
     }
 
     // assumes correct lazybase or will break
-    pub fn as_str(&self) -> String {
-        self.base.as_ref().base.borrow()[self.start..self.end].to_string()
+    pub fn as_str(&self) -> &str {
+        &self.base.as_ref().base[self.start..self.end]
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Searches the suffix array of the whole text rather than scanning.
+    pub fn contains(&self, needle: &str) -> bool {
+        self.base.contains_in(needle, self.start, self.end)
     }
 
-    pub fn contains(&self, base: &LazyBase, needle: &str) -> bool {
-        base.find_all(needle)
+    /// Ranges of this text matching `needle` once both are normalized,
+    /// relative to its start. Uses the normalized index of the whole text,
+    /// so the text itself is never normalized again.
+    pub fn find_normalized(
+        &self,
+        needle: &str,
+        normalization: Normalization,
+    ) -> Vec<(usize, usize)> {
+        self.base
+            .find_normalized(needle, normalization)
             .into_iter()
-            .any(|idx| idx >= self.start && idx < self.end)
+            .filter(|(start, end)| self.start <= *start && *end <= self.end)
+            .map(|(start, end)| (start - self.start, end - self.start))
+            .collect()
+    }
+}
+
+impl AsRef<str> for LazyStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for LazyStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for LazyStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for LazyStr {}
+
+impl PartialEq<str> for LazyStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for LazyStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<LazyStr> for str {
+    fn eq(&self, other: &LazyStr) -> bool {
+        self == other.as_str()
     }
 }
 
@@ -126,3 +262,48 @@ impl Default for LazyStr {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LazyBase, LazyStr, Normalization};
+    use std::sync::Arc;
+
+    fn lazy(base: &Arc<LazyBase>, start: usize, end: usize) -> LazyStr {
+        LazyStr::new(base.clone(), start, end)
+    }
+
+    #[test]
+    fn contains_stays_within_the_range() {
+        let base = Arc::new(LazyBase::init("abc abd abc".to_string()));
+        assert!(lazy(&base, 0, 3).contains("abc"));
+        assert!(!lazy(&base, 0, 2).contains("abc"));
+        assert!(!lazy(&base, 1, 7).contains("abc"));
+        assert!(lazy(&base, 4, 11).contains("abc"));
+        assert!(lazy(&base, 4, 4).contains(""));
+        assert!(!lazy(&base, 0, 11).contains("abe"));
+        assert_eq!(base.count("ab"), 3);
+    }
+
+    #[test]
+    fn contains_with_more_hits_than_bytes() {
+        // every position is a hit, so the range is scanned instead
+        let base = Arc::new(LazyBase::init("a".repeat(100) + "b"));
+        assert!(lazy(&base, 10, 12).contains("aa"));
+        assert!(!lazy(&base, 10, 11).contains("aa"));
+        assert!(lazy(&base, 99, 101).contains("ab"));
+    }
+
+    #[test]
+    fn find_normalized_is_relative_to_the_range() {
+        let base = Arc::new(LazyBase::init("Café cafe CAFÉ".to_string()));
+        let text = lazy(&base, 6, base.len());
+        assert_eq!(
+            text.find_normalized("cafe", Normalization::LOOSE),
+            [(0, 4), (5, 10)]
+        );
+        assert_eq!(
+            text.find_normalized("café", Normalization::CASE_INSENSITIVE),
+            [(5, 10)]
+        );
+    }
+}
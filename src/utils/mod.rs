@@ -1,9 +1,11 @@
 pub mod char_queue;
 pub mod lazy_str;
+pub mod normalize;
 pub mod parser;
 pub mod shared_pool;
 pub mod substring_finder;
 
 pub use char_queue::{CharQueue, ParseQueue};
-pub use lazy_str::{LazyBase, LazyStr};
-pub use shared_pool::{SharedPool, SharedStr};
+pub use lazy_str::{LazyBase, LazyBaseBuilder, LazyStr};
+pub use normalize::Normalization;
+pub use shared_pool::{Interner, LocalPool, SharedPool, SharedStr};
@@ -0,0 +1,189 @@
+use unicode_normalization::{
+    char::{canonical_combining_class, is_combining_mark},
+    is_nfc_quick, IsNormalized, UnicodeNormalization,
+};
+
+use super::substring_finder::StrFinder;
+
+/// How text is normalized before it's compared. Text is always brought to
+/// NFC, or NFKC with `compatibility`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Normalization {
+    pub case_fold: bool,
+    /// Compatibility forms, e.g. `ﬁ` matches `fi` and `²` matches `2`.
+    pub compatibility: bool,
+    /// Drops combining marks, `é` matches `e`.
+    pub strip_diacritics: bool,
+}
+
+impl Normalization {
+    pub const NFC: Normalization = Normalization {
+        case_fold: false,
+        compatibility: false,
+        strip_diacritics: false,
+    };
+
+    pub const CASE_INSENSITIVE: Normalization = Normalization {
+        case_fold: true,
+        compatibility: false,
+        strip_diacritics: false,
+    };
+
+    /// Everything folded: case, compatibility forms and diacritics.
+    pub const LOOSE: Normalization = Normalization {
+        case_fold: true,
+        compatibility: true,
+        strip_diacritics: true,
+    };
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for chunk in chunks(text) {
+            self.apply_chunk(chunk, &mut out);
+        }
+        out
+    }
+
+    fn apply_chunk(&self, chunk: &str, out: &mut String) {
+        let decomposed = match self.compatibility {
+            true => chunk.nfkd().collect::<String>(),
+            false => chunk.nfd().collect::<String>(),
+        };
+
+        let mut folded = String::with_capacity(decomposed.len());
+        for c in decomposed.chars() {
+            if self.strip_diacritics && is_combining_mark(c) {
+                continue;
+            }
+            match self.case_fold {
+                true => fold_case(c, &mut folded),
+                false => folded.push(c),
+            }
+        }
+        out.extend(folded.nfc());
+    }
+}
+
+// full case folding where it differs from lowercasing
+fn fold_case(c: char, out: &mut String) {
+    match c {
+        'ß' | 'ẞ' => out.push_str("ss"),
+        'ς' => out.push('σ'),
+        'ſ' => out.push('s'),
+        _ => out.extend(c.to_lowercase()),
+    }
+}
+
+// splits before every starter that can't compose with what precedes it, so
+// a chunk is a char with its combining marks, or a run of conjoining Hangul
+// jamo, and normalizes on its own
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices().skip(1);
+        let end = chars
+            .find(|(_, c)| {
+                canonical_combining_class(*c) == 0
+                    && is_nfc_quick(std::iter::once(*c)) != IsNormalized::Maybe
+            })
+            .map_or(rest.len(), |(idx, _)| idx);
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk).filter(|chunk| !chunk.is_empty())
+    })
+}
+
+/// Normalized copy of a text with a suffix array, able to map the offsets
+/// of matches back to the original text.
+#[derive(Debug, Clone)]
+pub struct NormalizedIndex {
+    normalization: Normalization,
+    text: String,
+    // range in the original text of the chunk each byte of `text` comes from
+    origins: Vec<(usize, usize)>,
+    str_finder: StrFinder,
+}
+
+impl NormalizedIndex {
+    pub fn new(original: &str, normalization: Normalization) -> Self {
+        let mut text = String::with_capacity(original.len());
+        let mut origins = Vec::with_capacity(original.len());
+        let mut offset = 0;
+        for chunk in chunks(original) {
+            normalization.apply_chunk(chunk, &mut text);
+            origins.resize(text.len(), (offset, offset + chunk.len()));
+            offset += chunk.len();
+        }
+
+        NormalizedIndex {
+            normalization,
+            str_finder: StrFinder::new(&text),
+            text,
+            origins,
+        }
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Ranges of the original text matching `needle` once both are
+    /// normalized, in order. Matches starting or ending inside an expanded
+    /// char cover all of it.
+    pub fn find_all(&self, needle: &str) -> Vec<(usize, usize)> {
+        let needle = self.normalization.apply(needle);
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        let mut ranges = self
+            .str_finder
+            .find_all(&self.text, &needle)
+            .into_iter()
+            .map(|start| {
+                let end = start + needle.len();
+                (self.origins[start].0, self.origins[end - 1].1)
+            })
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        ranges.dedup();
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Normalization, NormalizedIndex};
+
+    #[test]
+    fn decomposed_hangul_matches_precomposed() {
+        // 한국 as conjoining jamo, then as an LV syllable followed by a T jamo
+        let text = "가 \u{1112}\u{1161}\u{11ab}\u{1100}\u{116e}\u{11a8} 하\u{11ab}";
+        let index = NormalizedIndex::new(text, Normalization::NFC);
+
+        let jamo = text.find('\u{1112}').unwrap();
+        let syllable = text.rfind('하').unwrap();
+        assert_eq!(
+            index.find_all("한"),
+            [(jamo, jamo + 9), (syllable, text.len())]
+        );
+        assert_eq!(index.find_all("한국"), [(jamo, jamo + 18)]);
+        assert_eq!(Normalization::NFC.apply("\u{1112}\u{1161}\u{11ab}"), "한");
+    }
+
+    #[test]
+    fn ranges_cover_expanded_chars() {
+        let text = "Straße ﬁnal Café";
+        let index = NormalizedIndex::new(text, Normalization::LOOSE);
+
+        assert_eq!(index.find_all("STRASSE"), [(0, "Straße".len())]);
+        // `ﬁ` is one char of the text, matching half of it covers all of it
+        let fi = text.find('ﬁ').unwrap();
+        assert_eq!(index.find_all("inal"), [(fi, fi + "ﬁnal".len())]);
+        assert_eq!(
+            index.find_all("cafe"),
+            [(text.len() - "Café".len(), text.len())]
+        );
+        assert!(index.find_all("").is_empty());
+    }
+}
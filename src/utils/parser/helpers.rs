@@ -18,7 +18,10 @@ pub fn digit(input: &mut ParseQueue) -> PResult<usize> {
 pub fn alpha1(input: &mut ParseQueue) -> PResult<String> {
     let out = alpha0(input)?;
     match out.len() {
-        0 => Err(PError::InvalidChar(input.peek().unwrap())),
+        0 => match input.peek() {
+            Some(c) => Err(PError::InvalidChar(c)),
+            None => Err(PError::EndOfInput),
+        },
         _ => Ok(out),
     }
 }
@@ -30,13 +33,25 @@ pub fn alpha0(input: &mut ParseQueue) -> PResult<String> {
     }
 }
 
+// alphabetic char followed by alphanumerics, e.g. a tag name like `h1`
+pub fn name1(input: &mut ParseQueue) -> PResult<String> {
+    match input.peek() {
+        Some(c) if c.is_alphabetic() => Ok(input.consume_while(|c| c.is_alphanumeric())),
+        Some(c) => Err(PError::InvalidChar(c)),
+        None => Err(PError::EndOfInput),
+    }
+}
+
 pub fn whitespace1(input: &mut ParseQueue) -> PResult<String> {
     match input.len() {
         0 => Err(PError::EndOfInput),
         _ => {
             let str = input.consume_while(|c| c.is_whitespace());
             match str.len() {
-                0 => Err(PError::InvalidChar(input.peek().unwrap())),
+                0 => match input.peek() {
+                    Some(c) => Err(PError::InvalidChar(c)),
+                    None => Err(PError::EndOfInput),
+                },
                 _ => Ok(str),
             }
         }
@@ -5,29 +5,230 @@ use std::{
 
 pub type SharedStr = Arc<str>;
 
+/// Turns tag and attribute names into shared strings. Implementations must
+/// be usable from any thread, documents keep theirs for later mutations.
+pub trait Interner: Send + Sync {
+    fn intern(&self, input: &str) -> SharedStr;
+}
+
+#[rustfmt::skip]
+const ATOMS: &[&str] = &[
+    "#text",
+    // tags
+    "a", "abbr", "address", "area", "article", "aside", "audio", "b", "base", "bdi",
+    "bdo", "blockquote", "body", "br", "button", "canvas", "caption", "cite", "code",
+    "col", "colgroup", "data", "datalist", "dd", "del", "details", "dfn", "dialog",
+    "div", "dl", "dt", "em", "embed", "fieldset", "figcaption", "figure", "footer",
+    "form", "h1", "h2", "h3", "h4", "h5", "h6", "head", "header", "hgroup", "hr",
+    "html", "i", "iframe", "img", "input", "ins", "kbd", "label", "legend", "li",
+    "link", "main", "map", "mark", "menu", "meta", "meter", "nav", "noscript",
+    "object", "ol", "optgroup", "option", "output", "p", "param", "picture", "pre",
+    "progress", "q", "rp", "rt", "ruby", "s", "samp", "script", "search", "section",
+    "select", "slot", "small", "source", "span", "strong", "style", "sub", "summary",
+    "sup", "svg", "table", "tbody", "td", "template", "textarea", "tfoot", "th",
+    "thead", "time", "title", "tr", "track", "u", "ul", "var", "video", "wbr",
+    // attributes
+    "accept", "action", "align", "alt", "async", "autocomplete", "autofocus",
+    "charset", "checked", "class", "cols", "colspan", "content", "contenteditable",
+    "crossorigin", "defer", "dir", "disabled", "download", "draggable", "enctype",
+    "for", "height", "hidden", "href", "hreflang", "http-equiv", "id", "integrity",
+    "lang", "loading", "max", "maxlength", "media", "method", "min", "multiple",
+    "name", "novalidate", "onclick", "onload", "pattern", "placeholder", "poster",
+    "property", "readonly", "referrerpolicy", "rel", "required", "role", "rows",
+    "rowspan", "sandbox", "scope", "selected", "sizes", "spellcheck", "src",
+    "srcset", "start", "step", "tabindex", "target", "translate", "type", "value",
+    "width", "xmlns",
+];
+
+/// Pre-seeded name of a known HTML tag or attribute, found without locking.
+pub fn static_atom(name: &str) -> Option<SharedStr> {
+    static TABLE: OnceLock<HashSet<SharedStr>> = OnceLock::new();
+
+    TABLE
+        .get_or_init(|| ATOMS.iter().map(|&atom| Arc::from(atom)).collect())
+        .get(name)
+        .cloned()
+}
+
+/// Process wide pool of names, used unless a builder is given another
+/// interner. Known HTML names never reach the pool.
 #[derive(Debug)]
 pub struct SharedPool {
-    pool: Mutex<HashSet<Arc<str>>>,
+    pool: Mutex<PoolState>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    names: HashSet<SharedStr>,
+    limit: Option<usize>,
 }
 
 impl SharedPool {
-    pub fn instance() -> &'static Self {
-        static POOL: OnceLock<SharedPool> = OnceLock::new();
+    pub fn instance() -> &'static Arc<Self> {
+        static POOL: OnceLock<Arc<SharedPool>> = OnceLock::new();
 
-        POOL.get_or_init(|| SharedPool {
-            pool: HashSet::new().into(),
+        POOL.get_or_init(|| {
+            Arc::new(SharedPool {
+                pool: PoolState::default().into(),
+            })
         })
     }
 
     pub fn get_or_intern<S: AsRef<str>>(input: S) -> SharedStr {
-        let input = input.as_ref();
+        Self::instance().intern(input.as_ref())
+    }
+
+    /// Stops the pool from growing past `limit` names, further names are
+    /// allocated without being pooled. `None` removes the bound.
+    pub fn set_limit(limit: Option<usize>) {
+        Self::instance().pool.lock().unwrap().limit = limit;
+    }
+
+    /// Drops every pooled name. Strings still held by documents stay valid.
+    pub fn clear() {
         let mut pool = Self::instance().pool.lock().unwrap();
-        if let Some(name) = pool.get(input) {
-            name.clone()
-        } else {
-            let name: Arc<str> = Arc::from(input);
-            pool.insert(name.clone());
-            name
+        pool.names = HashSet::new();
+    }
+
+    pub fn len() -> usize {
+        Self::instance().pool.lock().unwrap().names.len()
+    }
+}
+
+impl Interner for SharedPool {
+    fn intern(&self, input: &str) -> SharedStr {
+        if let Some(atom) = static_atom(input) {
+            return atom;
+        }
+
+        let mut pool = self.pool.lock().unwrap();
+        if let Some(name) = pool.names.get(input) {
+            return name.clone();
+        }
+
+        let name: SharedStr = Arc::from(input);
+        if pool.limit.is_none_or(|limit| pool.names.len() < limit) {
+            pool.names.insert(name.clone());
         }
+        name
+    }
+}
+
+/// Interner owned by a single document, dropped along with it. Its lock is
+/// only taken by the parse and mutations of that document.
+#[derive(Debug, Default)]
+pub struct LocalPool {
+    names: Mutex<HashSet<SharedStr>>,
+}
+
+impl LocalPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Interner for LocalPool {
+    fn intern(&self, input: &str) -> SharedStr {
+        if let Some(atom) = static_atom(input) {
+            return atom;
+        }
+
+        let mut names = self.names.lock().unwrap();
+        match names.get(input) {
+            Some(name) => name.clone(),
+            None => {
+                let name: SharedStr = Arc::from(input);
+                names.insert(name.clone());
+                name
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::DomBuilder;
+
+    // tests changing the global pool must not overlap
+    static GLOBAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn known_names_are_static_atoms() {
+        let div = static_atom("div").unwrap();
+        assert!(Arc::ptr_eq(&div, &SharedPool::get_or_intern("div")));
+        assert!(Arc::ptr_eq(&div, &LocalPool::new().intern("div")));
+        assert!(static_atom("class").is_some());
+        assert!(static_atom("x-unknown").is_none());
+    }
+
+    #[test]
+    fn limit_stops_growth_without_breaking_lookups() {
+        let _global = GLOBAL.lock().unwrap();
+
+        SharedPool::set_limit(Some(0));
+        SharedPool::clear();
+        let first = SharedPool::get_or_intern("x-pool-limited");
+        let second = SharedPool::get_or_intern("x-pool-limited");
+        assert_eq!(&*first, "x-pool-limited");
+        assert_eq!(first, second);
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(SharedPool::len(), 0);
+
+        SharedPool::set_limit(None);
+        let first = SharedPool::get_or_intern("x-pool-unlimited");
+        assert!(Arc::ptr_eq(
+            &first,
+            &SharedPool::get_or_intern("x-pool-unlimited")
+        ));
+        assert!(SharedPool::len() >= 1);
+
+        SharedPool::clear();
+        assert_eq!(&*first, "x-pool-unlimited");
+        assert!(!Arc::ptr_eq(
+            &first,
+            &SharedPool::get_or_intern("x-pool-unlimited")
+        ));
+    }
+
+    #[test]
+    fn local_pool_counts_its_own_names() {
+        let pool = LocalPool::new();
+        assert!(pool.is_empty());
+
+        let first = pool.intern("x-local");
+        assert!(Arc::ptr_eq(&first, &pool.intern("x-local")));
+        pool.intern("span");
+        assert_eq!(pool.len(), 1);
+        assert!(!Arc::ptr_eq(&first, &LocalPool::new().intern("x-local")));
+    }
+
+    #[test]
+    fn local_interning_leaves_the_global_pool_alone() {
+        let _global = GLOBAL.lock().unwrap();
+        let input = r#"<xinterned data-interned="1">a</xinterned>"#;
+
+        let builder = DomBuilder::new();
+        builder.local_interning();
+        builder.feed(input);
+        let local = builder.finalize().unwrap();
+        let tag = local.root().tag();
+        assert_eq!(&**tag, "xinterned");
+        assert!(!Arc::ptr_eq(tag, &SharedPool::get_or_intern("xinterned")));
+
+        // the default builder shares its names through the global pool
+        let global = DomBuilder::parse(input).unwrap();
+        assert!(Arc::ptr_eq(
+            global.root().tag(),
+            &SharedPool::get_or_intern("xinterned")
+        ));
     }
 }
@@ -1,68 +1,139 @@
 #[derive(Debug, Clone)]
 pub struct StrFinder {
     suffix_arr: Vec<usize>,
+    // lcp[i] is the longest common prefix of the suffixes at i - 1 and i
+    lcp: Vec<usize>,
+}
+
+/// Substring statistics of a text, counted in bytes rather than chars: a
+/// substring is any range of bytes, even one splitting a char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubstringStats {
+    /// Length of the text in bytes.
+    pub len: usize,
+    /// Number of distinct non-empty byte ranges.
+    pub distinct: usize,
+    /// Length in bytes of the longest byte range occurring at least twice,
+    /// which can be longer than what `longest_repeated` returns.
+    pub longest_repeat: usize,
 }
 
 impl StrFinder {
     pub fn new(str: &str) -> Self {
-        let suffix_arr = make_suffix_array_by_induced_sorting(str.as_bytes(), 256);
-        StrFinder { suffix_arr }
+        let mut finder = StrFinder::default();
+        finder.change(str);
+        finder
     }
 
     pub fn change(&mut self, str: &str) {
         self.suffix_arr = make_suffix_array_by_induced_sorting(str.as_bytes(), 256);
+        self.lcp = kasai(str.as_bytes(), &self.suffix_arr);
     }
 
+    /// Positions of every occurrence of `needle`, in suffix order.
     pub fn find_all(&self, haystack: &str, needle: &str) -> Vec<usize> {
-        let mut left = 0;
-        let mut right = self.suffix_arr.len();
-
-        while left < right {
-            let mid = (left + right) / 2;
-            let start = self.suffix_arr[mid];
-            let suffix = &haystack[start..];
-
-            if suffix.starts_with(needle) {
-                return self.find_matched(haystack, needle, mid);
-            } else if suffix < needle {
-                left = mid + 1;
-            } else {
-                right = mid;
-            }
-        }
+        self.occurrences(haystack, needle).to_vec()
+    }
+
+    /// Like `find_all`, borrowing the positions from the suffix array.
+    pub fn occurrences(&self, haystack: &str, needle: &str) -> &[usize] {
+        let (start, end) = self.range(haystack, needle);
+        &self.suffix_arr[start..end]
+    }
 
-        Vec::new()
+    pub fn count(&self, haystack: &str, needle: &str) -> usize {
+        let (start, end) = self.range(haystack, needle);
+        end - start
     }
 
-    fn find_matched(&self, haystack: &str, needle: &str, mid: usize) -> Vec<usize> {
-        let mut matched = Vec::new();
+    // range of the suffix array starting with `needle`, found with two binary
+    // searches comparing at most `needle.len()` bytes each, on bytes as
+    // slicing the str could split a char
+    fn range(&self, haystack: &str, needle: &str) -> (usize, usize) {
+        let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+        let prefix = |pos: usize| &haystack[pos..haystack.len().min(pos + needle.len())];
 
-        for i in (0..mid).rev() {
-            let idx = self.suffix_arr[i];
-            let suffix = &haystack[idx..];
-            match suffix.starts_with(needle) {
-                true => matched.push(idx),
-                false => break,
-            }
+        let start = self.suffix_arr.partition_point(|pos| prefix(*pos) < needle);
+        let end = start + self.suffix_arr[start..].partition_point(|pos| prefix(*pos) <= needle);
+        (start, end)
+    }
+
+    /// The longest substring occurring at least twice, occurrences may overlap.
+    pub fn longest_repeated<'h>(&self, haystack: &'h str) -> Option<&'h str> {
+        // suffixes starting inside a char are skipped, and the common prefix
+        // of bytes may end inside one
+        let (start, end) = self
+            .lcp
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| haystack.is_char_boundary(self.suffix_arr[*idx]))
+            .map(|(idx, len)| {
+                let start = self.suffix_arr[idx];
+                let mut end = start + len;
+                while !haystack.is_char_boundary(end) {
+                    end -= 1;
+                }
+                (start, end)
+            })
+            .enumerate()
+            .max_by_key(|(idx, (start, end))| (end - start, std::cmp::Reverse(*idx)))?
+            .1;
+        Some(&haystack[start..end]).filter(|repeat| !repeat.is_empty())
+    }
+
+    pub fn stats(&self) -> SubstringStats {
+        // the suffix array holds the empty suffix as well
+        let len = self.suffix_arr.len().saturating_sub(1);
+        let shared = self.lcp.iter().sum::<usize>();
+        SubstringStats {
+            len,
+            distinct: len * (len + 1) / 2 - shared,
+            longest_repeat: self.lcp.iter().copied().max().unwrap_or(0),
         }
+    }
+}
 
-        matched.push(self.suffix_arr[mid]);
+// Kasai's algorithm, linear in the length of the string
+fn kasai(string: &[u8], suffix_arr: &[usize]) -> Vec<usize> {
+    let mut rank = vec![0; suffix_arr.len()];
+    for (idx, pos) in suffix_arr.iter().enumerate() {
+        rank[*pos] = idx;
+    }
 
-        for i in (mid + 1)..self.suffix_arr.len() {
-            let idx = self.suffix_arr[i];
-            let suffix = &haystack[idx..];
-            match suffix.starts_with(needle) {
-                true => matched.push(idx),
-                false => break,
-            }
+    let mut lcp = vec![0; suffix_arr.len()];
+    let mut len: usize = 0;
+    for pos in 0..string.len() {
+        let idx = rank[pos];
+        if idx == 0 {
+            len = 0;
+            continue;
         }
 
-        matched
+        let prev = suffix_arr[idx - 1];
+        while pos + len < string.len()
+            && prev + len < string.len()
+            && string[pos + len] == string[prev + len]
+        {
+            len += 1;
+        }
+        lcp[idx] = len;
+        len = len.saturating_sub(1);
     }
+    lcp
 }
 
+// symbols of the sorted string, bytes at the top level and lms names when
+// recursing, which can outnumber the 256 values of a byte
+trait Symbol: Copy + Ord + Into<usize> {}
+
+impl Symbol for u8 {}
+impl Symbol for usize {}
+
 // default alphabet_size = 256 (u8)
-fn make_suffix_array_by_induced_sorting(string: &[u8], alphabet_size: usize) -> Vec<usize> {
+fn make_suffix_array_by_induced_sorting<T: Symbol>(
+    string: &[T],
+    alphabet_size: usize,
+) -> Vec<usize> {
     let typemap = build_type_map(string);
     let bucket_sizes = find_bucket_sizes(string, alphabet_size);
     let mut guessed_suffix_array = guess_lms_sort(string, &bucket_sizes, &typemap);
@@ -88,7 +159,7 @@ fn make_suffix_array_by_induced_sorting(string: &[u8], alphabet_size: usize) ->
 }
 
 // False -> S-type, True -> L-type
-fn build_type_map(data: &[u8]) -> Vec<bool> {
+fn build_type_map<T: Symbol>(data: &[T]) -> Vec<bool> {
     let mut res = vec![false; data.len() + 1];
     if data.len() == 0 {
         return res;
@@ -107,10 +178,10 @@ fn build_type_map(data: &[u8]) -> Vec<bool> {
     res
 }
 
-fn find_bucket_sizes(str: &[u8], alphabet_size: usize) -> Vec<usize> {
+fn find_bucket_sizes<T: Symbol>(str: &[T], alphabet_size: usize) -> Vec<usize> {
     let mut res = vec![0; alphabet_size];
     for char in str {
-        res[*char as usize] += 1
+        res[(*char).into()] += 1
     }
 
     res
@@ -138,7 +209,11 @@ fn find_bucket_tails(bucket_sizes: &Vec<usize>) -> Vec<usize> {
     res
 }
 
-fn guess_lms_sort(string: &[u8], bucket_sizes: &Vec<usize>, typemap: &Vec<bool>) -> Vec<usize> {
+fn guess_lms_sort<T: Symbol>(
+    string: &[T],
+    bucket_sizes: &Vec<usize>,
+    typemap: &Vec<bool>,
+) -> Vec<usize> {
     let mut guessed_suffix_array: Vec<usize> = vec![usize::MAX; string.len() + 1];
     let mut bucket_tails = find_bucket_tails(bucket_sizes);
 
@@ -147,7 +222,7 @@ fn guess_lms_sort(string: &[u8], bucket_sizes: &Vec<usize>, typemap: &Vec<bool>)
             continue;
         }
 
-        let bucket_index = string[i] as usize;
+        let bucket_index = string[i].into();
         guessed_suffix_array[bucket_tails[bucket_index]] = i;
         bucket_tails[bucket_index] -= 1;
     }
@@ -167,8 +242,8 @@ fn is_lms_char(offset: usize, typemap: &Vec<bool>) -> bool {
     return false;
 }
 
-fn induced_sort_l(
-    string: &[u8],
+fn induced_sort_l<T: Symbol>(
+    string: &[T],
     guessed_suffix_array: &mut Vec<usize>,
     bucket_sizes: &Vec<usize>,
     typemap: &Vec<bool>,
@@ -190,14 +265,14 @@ fn induced_sort_l(
             continue;
         }
 
-        let bucket_index = string[j] as usize;
+        let bucket_index = string[j].into();
         guessed_suffix_array[bucket_heads[bucket_index]] = j;
         bucket_heads[bucket_index] += 1;
     }
 }
 
-fn induced_sort_s(
-    string: &[u8],
+fn induced_sort_s<T: Symbol>(
+    string: &[T],
     guessed_suffix_array: &mut Vec<usize>,
     bucket_sizes: &Vec<usize>,
     typemap: &Vec<bool>,
@@ -215,14 +290,14 @@ fn induced_sort_s(
             continue;
         }
 
-        let bucket_index = string[j] as usize;
+        let bucket_index = string[j].into();
         guessed_suffix_array[bucket_tails[bucket_index]] = j;
         bucket_tails[bucket_index] -= 1;
     }
 }
 
-fn lms_substrings_are_equal(
-    string: &[u8],
+fn lms_substrings_are_equal<T: Symbol>(
+    string: &[T],
     typemap: &Vec<bool>,
     offset_a: usize,
     offset_b: usize,
@@ -252,8 +327,8 @@ fn lms_substrings_are_equal(
     }
 }
 
-fn summarize_suffix_array(
-    string: &[u8],
+fn summarize_suffix_array<T: Symbol>(
+    string: &[T],
     guessed_suffix_array: &mut Vec<usize>,
     typemap: &Vec<bool>,
 ) -> (Vec<usize>, usize, Vec<usize>) {
@@ -314,21 +389,15 @@ fn make_summary_suffix_array(
             summary_suffix_array[y + 1] = i;
         }
     } else {
-        summary_suffix_array = make_suffix_array_by_induced_sorting(
-            summary_string
-                .into_iter()
-                .map(|v| v as u8)
-                .collect::<Vec<u8>>()
-                .as_slice(),
-            summary_alphabet_size,
-        );
+        summary_suffix_array =
+            make_suffix_array_by_induced_sorting(&summary_string, summary_alphabet_size);
     }
 
     return summary_suffix_array;
 }
 
-fn accurate_lms_sort(
-    string: &[u8],
+fn accurate_lms_sort<T: Symbol>(
+    string: &[T],
     bucket_sizes: &Vec<usize>,
     summary_suffix_array: Vec<usize>,
     summary_suffix_offsets: Vec<usize>,
@@ -338,7 +407,7 @@ fn accurate_lms_sort(
 
     for i in (2..summary_suffix_array.len()).rev() {
         let string_index = summary_suffix_offsets[summary_suffix_array[i]];
-        let bucket_index = string[string_index] as usize;
+        let bucket_index = string[string_index].into();
 
         suffix_offsets[bucket_tails[bucket_index]] = string_index;
         bucket_tails[bucket_index] -= 1;
@@ -352,6 +421,102 @@ impl Default for StrFinder {
     fn default() -> Self {
         StrFinder {
             suffix_arr: Vec::new(),
+            lcp: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StrFinder, SubstringStats};
+
+    fn brute_force(haystack: &str, needle: &str) -> Vec<usize> {
+        (0..=haystack.len().saturating_sub(needle.len()))
+            .filter(|&i| haystack.as_bytes()[i..].starts_with(needle.as_bytes()))
+            .collect()
+    }
+
+    fn sorted(mut positions: Vec<usize>) -> Vec<usize> {
+        positions.sort_unstable();
+        positions
+    }
+
+    // long enough for the reduced string to have more than 256 names
+    #[test]
+    fn finds_all_in_large_text() {
+        let mut seed = 7u32;
+        let text = (0..20_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (b'a' + (seed >> 16) as u8 % 6) as char
+            })
+            .collect::<String>();
+        let finder = StrFinder::new(&text);
+
+        for start in (0..text.len() - 8).step_by(997) {
+            for len in [1, 3, 8] {
+                let needle = &text[start..start + len];
+                assert_eq!(
+                    sorted(finder.find_all(&text, needle)),
+                    brute_force(&text, needle),
+                    "{needle}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counts_and_repeats() {
+        let text = "banana";
+        let finder = StrFinder::new(text);
+        assert_eq!(finder.count(text, "ana"), 2);
+        assert_eq!(finder.count(text, "a"), 3);
+        assert_eq!(finder.count(text, "nab"), 0);
+        assert_eq!(finder.longest_repeated(text), Some("ana"));
+        assert_eq!(
+            finder.stats(),
+            SubstringStats {
+                len: 6,
+                distinct: 15,
+                longest_repeat: 3,
+            }
+        );
+
+        let finder = StrFinder::new("abc");
+        assert_eq!(finder.longest_repeated("abc"), None);
+    }
+
+    #[test]
+    fn longest_repeated_in_non_ascii_text() {
+        for (text, repeat) in [
+            ("éab ©ab", Some("ab")),
+            ("é é", Some("é")),
+            ("éè", None),
+            ("©ab ®ab ©ab", Some("©ab")),
+            ("日本語と日本", Some("日本")),
+        ] {
+            let finder = StrFinder::new(text);
+            assert_eq!(finder.longest_repeated(text), repeat, "{text}");
+        }
+
+        let text = "éab ©ab";
+        let stats = StrFinder::new(text).stats();
+        assert_eq!(stats.len, text.len());
+        // "ab" and the trailing byte shared by é and ©
+        assert_eq!(stats.longest_repeat, 3);
+    }
+
+    #[test]
+    fn finds_all_in_non_ascii_text() {
+        let text = "café crème, déjà vu, café";
+        let finder = StrFinder::new(text);
+
+        for needle in ["é", "café", "è", "à v", "e", "zz"] {
+            assert_eq!(
+                sorted(finder.find_all(text, needle)),
+                brute_force(text, needle),
+                "{needle}"
+            );
         }
     }
 }
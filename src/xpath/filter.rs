@@ -1,6 +1,7 @@
-use std::{iter::Rev, slice::Iter};
-
-use crate::dom::{node::AttributeValue, DomNode};
+use crate::{
+    dom::{arena::NodeId, node::AttributeRef, DomNode},
+    utils::Normalization,
+};
 
 use super::{
     parser::interface::{Axis, Condition, LogicalOperator, Predicate, SpType, XPathStep},
@@ -13,6 +14,7 @@ pub enum AxisIterator<'a> {
     Descendant(DescendantIterator<'a>),
     RevChild(RevChildIterator<'a>),
     RevDescendant(RevDescendantIterator<'a>),
+    Indexed(IndexedIterator<'a>),
 }
 
 impl<'a> AxisIterator<'a> {
@@ -29,18 +31,19 @@ impl<'a> AxisIterator<'a> {
         }
     }
 
-    pub fn add(&mut self, node: &'a DomNode) {
+    pub fn add(&mut self, node: DomNode<'a>) {
         match self {
             Self::Child(child) => child.add(node),
             Self::Descendant(descendant) => descendant.add(node),
             Self::RevChild(child) => child.add(node),
             Self::RevDescendant(descendant) => descendant.add(node),
+            Self::Indexed(_) => {}
         }
     }
 }
 
 impl<'a> Iterator for AxisIterator<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
@@ -48,120 +51,221 @@ impl<'a> Iterator for AxisIterator<'a> {
             AxisIterator::Descendant(iter) => iter.next(),
             AxisIterator::RevChild(iter) => iter.next(),
             AxisIterator::RevDescendant(iter) => iter.next(),
+            AxisIterator::Indexed(iter) => iter.next(),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct ChildIterator<'a> {
-    children: std::slice::Iter<'a, DomNode>,
+    next: Option<DomNode<'a>>,
 }
 
 impl<'a> ChildIterator<'a> {
     pub fn new() -> Self {
-        Self {
-            children: std::slice::Iter::default(),
-        }
+        Self { next: None }
     }
 
-    pub fn add(&mut self, node: &'a DomNode) {
-        self.children = node.children.iter();
+    pub fn add(&mut self, node: DomNode<'a>) {
+        self.next = node.first_child();
     }
 }
 
 impl<'a> Iterator for ChildIterator<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.children.next()
+        let node = self.next?;
+        self.next = node.next_sibling();
+        Some(node)
     }
 }
 
-#[derive(Debug)]
+/// Pre-order walk over the elements of the subtree of a root, the root
+/// included.
+#[derive(Debug, Clone)]
 pub struct DescendantIterator<'a> {
-    stack: Vec<&'a DomNode>,
+    root: Option<DomNode<'a>>,
+    next: Option<DomNode<'a>>,
 }
 
 impl<'a> DescendantIterator<'a> {
     pub fn new() -> Self {
-        Self { stack: Vec::new() }
+        Self {
+            root: None,
+            next: None,
+        }
+    }
+
+    pub fn from_root(root: DomNode<'a>) -> Self {
+        let mut iter = Self::new();
+        iter.add(root);
+        iter
     }
 
-    pub fn add(&mut self, node: &'a DomNode) {
-        self.stack.push(node);
+    pub fn add(&mut self, node: DomNode<'a>) {
+        self.root = Some(node);
+        self.next = Some(node);
+    }
+
+    fn advance(&self, node: DomNode<'a>) -> Option<DomNode<'a>> {
+        if let Some(child) = node.first_child() {
+            return Some(child);
+        }
+
+        let root = self.root?;
+        let mut node = node;
+        while node.node_id() != root.node_id() {
+            if let Some(sibling) = node.next_sibling() {
+                return Some(sibling);
+            }
+            node = node.parent()?;
+        }
+
+        None
     }
 }
 
 impl<'a> Iterator for DescendantIterator<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(node) = self.stack.pop() {
-            self.stack.extend(node.children.iter().rev());
-            Some(node)
-        } else {
-            None
-        }
+        let node = self.next?;
+        self.next = self.advance(node);
+        Some(node)
     }
 }
 
 #[derive(Debug)]
 pub struct RevChildIterator<'a> {
-    children: Rev<Iter<'a, DomNode>>,
+    next: Option<DomNode<'a>>,
 }
 
 impl<'a> RevChildIterator<'a> {
     pub fn new() -> Self {
-        Self {
-            children: Rev::default(),
-        }
+        Self { next: None }
     }
 
-    pub fn add(&mut self, node: &'a DomNode) {
-        self.children = node.children.iter().rev();
+    pub fn add(&mut self, node: DomNode<'a>) {
+        self.next = node.last_child();
     }
 }
 
 impl<'a> Iterator for RevChildIterator<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.children.next()
+        let node = self.next?;
+        self.next = node.prev_sibling();
+        Some(node)
     }
 }
 
-#[derive(Debug)]
+/// `DescendantIterator` backwards, ending with the root.
+#[derive(Debug, Clone)]
 pub struct RevDescendantIterator<'a> {
-    stack: Vec<(&'a DomNode, bool)>,
+    root: Option<DomNode<'a>>,
+    next: Option<DomNode<'a>>,
 }
 
 impl<'a> RevDescendantIterator<'a> {
     pub fn new() -> Self {
-        Self { stack: vec![] }
+        Self {
+            root: None,
+            next: None,
+        }
     }
 
-    pub fn add(&mut self, node: &'a DomNode) {
-        self.stack.push((node, false));
+    pub fn from_root(root: DomNode<'a>) -> Self {
+        let mut iter = Self::new();
+        iter.add(root);
+        iter
+    }
+
+    pub fn add(&mut self, node: DomNode<'a>) {
+        self.root = Some(node);
+        self.next = Some(Self::last_descendant(node));
+    }
+
+    fn last_descendant(mut node: DomNode<'a>) -> DomNode<'a> {
+        while let Some(child) = node.last_child() {
+            node = child;
+        }
+        node
     }
 }
 
 impl<'a> Iterator for RevDescendantIterator<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some((node, processed)) = self.stack.pop() {
-            if processed {
-                return Some(node);
-            } else {
-                self.stack.push((node, true));
-
-                for child in node.children.iter() {
-                    self.stack.push((child, false));
-                }
-            }
+        let node = self.next?;
+        self.next = match self
+            .root
+            .is_some_and(|root| root.node_id() == node.node_id())
+        {
+            true => None,
+            false => match node.prev_sibling() {
+                Some(prev) => Some(Self::last_descendant(prev)),
+                None => node.parent(),
+            },
+        };
+        Some(node)
+    }
+}
+
+// candidates of a leading descendant step taken from the document index,
+// which lists the elements of the whole document in order
+#[derive(Debug)]
+pub struct IndexedIterator<'a> {
+    root: DomNode<'a>,
+    ids: &'a [NodeId],
+    rev: bool,
+}
+
+impl<'a> IndexedIterator<'a> {
+    pub fn new(
+        step: &XPathStep,
+        root: DomNode<'a>,
+        normalization: Option<Normalization>,
+    ) -> Option<Self> {
+        let doc = root.document();
+        let index = doc.index()?;
+        if !matches!(step.axis, Axis::Descendant) || root.node_id() != doc.root().node_id() {
+            return None;
         }
 
-        None
+        let id = step.predicates.iter().find_map(|p| match p {
+            Predicate::Expression(Condition::AttrCond {
+                attr,
+                sp_type: SpType::Base,
+                val,
+            }) if attr == "id" && normalization.is_none() => Some(val),
+            _ => None,
+        });
+        let ids = match id {
+            Some(id) => index.by_id(id),
+            None => index.by_tag(&step.tag_name),
+        };
+
+        Some(Self {
+            root,
+            ids,
+            rev: step.pos.as_ref().is_some_and(|p| !p.start),
+        })
+    }
+}
+
+impl<'a> Iterator for IndexedIterator<'a> {
+    type Item = DomNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, rest) = match self.rev {
+            true => self.ids.split_last(),
+            false => self.ids.split_first(),
+        }?;
+        self.ids = rest;
+        self.root.document().node(*id)
     }
 }
 
@@ -169,10 +273,11 @@ impl<'a> Iterator for RevDescendantIterator<'a> {
 pub struct XPathFilter<'a> {
     chain: Vec<(AxisIterator<'a>, usize)>,
     steps: Vec<&'a XPathStep>,
+    normalization: Option<Normalization>,
 }
 
 impl<'a> XPathFilter<'a> {
-    pub fn new_with_node(xpath: &'a XPath, node: &'a DomNode) -> Self {
+    pub fn new_with_node(xpath: &'a XPath, node: DomNode<'a>) -> Self {
         let mut filter = Self::new(xpath);
         filter.add_node(node);
         filter
@@ -190,21 +295,32 @@ impl<'a> XPathFilter<'a> {
                 acc
             });
 
-        Self { chain, steps }
+        Self {
+            chain,
+            steps,
+            normalization: xpath.normalization,
+        }
     }
 
-    pub fn add_node(&mut self, node: &'a DomNode) {
-        self.chain[0].0.add(node);
+    pub fn add_node(&mut self, node: DomNode<'a>) {
+        match IndexedIterator::new(self.steps[0], node, self.normalization) {
+            Some(iter) => self.chain[0].0 = AxisIterator::Indexed(iter),
+            None => self.chain[0].0.add(node),
+        }
     }
 
     // get or resolves the iterator at pos
-    pub fn get_resolve(&mut self, pos: usize) -> Option<&'a DomNode> {
+    pub fn get_resolve(&mut self, pos: usize) -> Option<DomNode<'a>> {
         {
             let step = self.steps.get(pos).unwrap();
+            let normalization = self.normalization;
             let (iter, node_pos) = self.chain.get_mut(pos).unwrap();
             while let Some(node) = iter.next() {
-                if node.tag.as_ref() == step.tag_name
-                    && step.predicates.iter().all(|p| p.evaluate(node))
+                if node.tag().as_ref() == step.tag_name
+                    && step
+                        .predicates
+                        .iter()
+                        .all(|p| p.evaluate(&node, normalization))
                 {
                     *node_pos += 1;
                     if step.pos.as_ref().map_or(true, |pos| pos.pos == *node_pos) {
@@ -226,7 +342,7 @@ impl<'a> XPathFilter<'a> {
 }
 
 impl<'a> Iterator for XPathFilter<'a> {
-    type Item = &'a DomNode;
+    type Item = DomNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.get_resolve(self.chain.len() - 1)
@@ -234,36 +350,103 @@ impl<'a> Iterator for XPathFilter<'a> {
 }
 
 impl Predicate {
-    pub fn evaluate(&self, node: &DomNode) -> bool {
+    pub fn evaluate(&self, node: &DomNode, normalization: Option<Normalization>) -> bool {
         match self {
-            Predicate::Expression(cond) => cond.evaluate(node),
+            Predicate::Expression(cond) => cond.evaluate(node, normalization),
             Predicate::Logical { op, left, right } => match op {
-                LogicalOperator::And => left.evaluate(node) && right.evaluate(node),
-                LogicalOperator::Or => left.evaluate(node) || right.evaluate(node),
+                LogicalOperator::And => {
+                    left.evaluate(node, normalization) && right.evaluate(node, normalization)
+                }
+                LogicalOperator::Or => {
+                    left.evaluate(node, normalization) || right.evaluate(node, normalization)
+                }
+            },
+        }
+    }
+
+    pub fn evaluate_attrs<'b, I>(&self, attributes: I, normalization: Option<Normalization>) -> bool
+    where
+        I: Iterator<Item = AttributeRef<'b>> + Clone,
+    {
+        match self {
+            Predicate::Expression(cond) => cond.evaluate_attrs(attributes, normalization),
+            Predicate::Logical { op, left, right } => match op {
+                LogicalOperator::And => {
+                    left.evaluate_attrs(attributes.clone(), normalization)
+                        && right.evaluate_attrs(attributes, normalization)
+                }
+                LogicalOperator::Or => {
+                    left.evaluate_attrs(attributes.clone(), normalization)
+                        || right.evaluate_attrs(attributes, normalization)
+                }
             },
         }
     }
 }
 
 impl Condition {
-    pub fn evaluate(&self, node: &DomNode) -> bool {
+    pub fn evaluate(&self, node: &DomNode, normalization: Option<Normalization>) -> bool {
         match self {
-            Condition::AttrExists(attr) => node.attributes.iter().any(|a| a.name.as_ref() == *attr),
-            Condition::AttrCond { attr, sp_type, val } => node.attributes.iter().any(|a| {
+            Condition::TextCond { sp_type, val } => match (sp_type, normalization) {
+                // the suffix array answers without scanning the deep text
+                (SpType::Contains, None) => node.text_content().contains(val),
+                (_, None) => sp_equal(node.text().trim(), sp_type, val, None),
+                (_, Some(normalization)) => text_matches(node, sp_type, val, normalization),
+            },
+            Condition::Flag(name) => node.has_flag(name),
+            _ => self.evaluate_attrs(node.attributes(), normalization),
+        }
+    }
+
+    // evaluates the condition against attributes only, text and flag
+    // conditions never match
+    pub fn evaluate_attrs<'b, I>(
+        &self,
+        mut attributes: I,
+        normalization: Option<Normalization>,
+    ) -> bool
+    where
+        I: Iterator<Item = AttributeRef<'b>>,
+    {
+        match self {
+            Condition::AttrExists(attr) => attributes.any(|a| a.name.as_ref() == *attr),
+            Condition::AttrCond { attr, sp_type, val } => attributes.any(|a| {
                 a.name.as_ref() == attr
-                    && match a.value {
-                        AttributeValue::Literal(ref v) => sp_equal(v, sp_type, val),
-                        _ => false,
-                    }
+                    && a.value
+                        .is_some_and(|v| sp_equal(v, sp_type, val, normalization))
             }),
-            Condition::TextCond { sp_type, val } => {
-                sp_equal(&node.text_content.as_str(), sp_type, val)
-            }
+            Condition::TextCond { .. } | Condition::Flag(_) => false,
         }
     }
 }
 
-fn sp_equal(left: &str, sp: &SpType, right: &str) -> bool {
+// compares the deep text of `node` with the ranges of the document's
+// normalized index, rather than normalizing the text of every candidate
+fn text_matches(node: &DomNode, sp: &SpType, val: &str, normalization: Normalization) -> bool {
+    if normalization.apply(val).is_empty() {
+        return sp_equal(node.text().trim(), sp, val, Some(normalization));
+    }
+
+    // spans of elements can take in the space separating them from their
+    // neighbours
+    let text = node.text_content();
+    let start = text.len() - text.as_str().trim_start().len();
+    let end = text.as_str().trim_end().len();
+    let ranges = text.find_normalized(val, normalization);
+    match sp {
+        SpType::Base => ranges.contains(&(start, end)),
+        SpType::Contains => !ranges.is_empty(),
+        SpType::Starts => ranges.iter().any(|range| range.0 == start),
+        SpType::Ends => ranges.iter().any(|range| range.1 == end),
+    }
+}
+
+fn sp_equal(left: &str, sp: &SpType, right: &str, normalization: Option<Normalization>) -> bool {
+    if let Some(normalization) = normalization {
+        let (left, right) = (normalization.apply(left), normalization.apply(right));
+        return sp_equal(&left, sp, &right, None);
+    }
+
     match sp {
         SpType::Base => left == right,
         SpType::Contains => left.contains(&right),
@@ -271,3 +454,183 @@ fn sp_equal(left: &str, sp: &SpType, right: &str) -> bool {
         SpType::Ends => left.ends_with(&right),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AxisIterator, XPathFilter};
+    use crate::{
+        dom::{Document, DomBuilder},
+        utils::Normalization,
+        xpath::XPathBuilder,
+    };
+
+    fn texts(
+        document: &Document,
+        xpath: &str,
+        normalization: Option<Normalization>,
+    ) -> Vec<String> {
+        let mut xpath = XPathBuilder::parse(xpath).unwrap();
+        if let Some(normalization) = normalization {
+            xpath = xpath.normalized(normalization);
+        }
+        let nodes = document.query(&xpath).collect::<Vec<_>>();
+        nodes
+            .iter()
+            .map(|node| node.text().trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn text_predicates_use_the_suffix_array() {
+        let document = DomBuilder::parse(
+            "<div><p>Price: <b>10</b> EUR</p><p>Price 20</p><p>no price</p></div>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            texts(&document, "//p[text*=\"Price: 10\"]", None),
+            ["Price: 10 EUR"]
+        );
+        assert_eq!(
+            texts(&document, "//p[text^=\"Price\"]", None),
+            ["Price: 10 EUR", "Price 20"]
+        );
+        assert_eq!(texts(&document, "//p[text$=\"price\"]", None), ["no price"]);
+        assert_eq!(texts(&document, "//b[text=\"10\"]", None), ["10"]);
+        assert!(texts(&document, "//b[text*=\"EUR\"]", None).is_empty());
+    }
+
+    #[test]
+    fn normalized_text_predicates() {
+        let document = DomBuilder::parse(
+            "<div><p>Café <b>CRÈME</b></p><p>cafe creme</p><p>Straße</p><p>ﬁne</p></div>",
+        )
+        .unwrap();
+        let loose = Some(Normalization::LOOSE);
+
+        assert_eq!(
+            texts(&document, "//p[text=\"cafe creme\"]", loose),
+            ["Café CRÈME", "cafe creme"]
+        );
+        assert_eq!(
+            texts(
+                &document,
+                "//p[text*=\"é c\"]",
+                Some(Normalization::CASE_INSENSITIVE)
+            ),
+            ["Café CRÈME"]
+        );
+        assert_eq!(texts(&document, "//p[text^=\"STRAS\"]", loose), ["Straße"]);
+        assert_eq!(texts(&document, "//p[text$=\"ine\"]", loose), ["ﬁne"]);
+        assert_eq!(texts(&document, "//b[text=\"creme\"]", loose), ["CRÈME"]);
+        // a match must lie within the node
+        assert!(texts(&document, "//b[text*=\"cafe\"]", loose).is_empty());
+        assert!(texts(&document, "//p[text=\"cafe\"]", loose).is_empty());
+    }
+
+    #[test]
+    fn normalized_text_predicates_on_hangul() {
+        let document = DomBuilder::parse(
+            "<ul><li>\u{1112}\u{1161}\u{11ab}\u{1100}\u{116e}\u{11a8}</li><li>일본</li></ul>",
+        )
+        .unwrap();
+        assert_eq!(
+            texts(&document, "//li[text=\"한국\"]", Some(Normalization::NFC)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn normalized_text_predicates_after_mutations() {
+        let mut document = DomBuilder::parse("<div><p>one</p><p>two</p></div>").unwrap();
+        let p = document.root().children().next().unwrap().node_id();
+        document.set_text(p, "ÜBER").unwrap();
+        assert_eq!(
+            texts(&document, "//p[text=\"uber\"]", Some(Normalization::LOOSE)),
+            ["ÜBER"]
+        );
+    }
+
+    const PAGE: &str = "<div id=\"top\"><p class=\"a  b\">one</p><section><p id=\"x\">two</p>\
+        <div><p class=\"b\">three</p></div></section><p id=\"x\" class=\"a\">four</p></div>";
+
+    fn indexed(input: &str) -> Document {
+        let builder = DomBuilder::new();
+        builder.enable_index();
+        builder.feed(input);
+        builder.finalize().unwrap()
+    }
+
+    #[test]
+    fn index_gives_the_same_results() {
+        let plain = DomBuilder::parse(PAGE).unwrap();
+        let indexed = indexed(PAGE);
+        assert!(!plain.has_index());
+        assert!(indexed.has_index());
+
+        for xpath in [
+            "//p",
+            "//p:nth=2",
+            "//p:nth=-1",
+            "//p:nth=9",
+            "//div",
+            "//div:nth=-1",
+            "//p[@id=\"x\"]",
+            "//p[@id=\"x\"]:nth=-1",
+            "//div[@id=\"top\"]",
+            "//div[@id=\"x\"]",
+            "//p[@id=\"missing\"]",
+            "//p[@class*=\"b\"]",
+            "//p[@class=\"a\"]",
+            "//p[@class^=\"a\" & @id]",
+            "//div//p",
+            "//section/p",
+            "//table",
+        ] {
+            assert_eq!(
+                texts(&indexed, xpath, None),
+                texts(&plain, xpath, None),
+                "{xpath}"
+            );
+        }
+        assert_eq!(
+            texts(&indexed, "//p[@id=\"x\"]", Some(Normalization::LOOSE)),
+            ["two", "four"]
+        );
+        assert_eq!(
+            texts(&indexed, "//p", None),
+            ["one", "two", "three", "four"]
+        );
+        assert_eq!(texts(&indexed, "//p:nth=-1", None), ["four"]);
+    }
+
+    #[test]
+    fn leading_descendant_step_uses_the_index() {
+        let indexed = indexed(PAGE);
+        let plain = DomBuilder::parse(PAGE).unwrap();
+        let uses_index = |document: &Document, xpath: &str, from_root: bool| {
+            let xpath = XPathBuilder::parse(xpath).unwrap();
+            let node = match from_root {
+                true => document.root(),
+                false => document.root().children().nth(1).unwrap(),
+            };
+            let filter = XPathFilter::new_with_node(&xpath, node);
+            matches!(filter.chain[0].0, AxisIterator::Indexed(_))
+        };
+
+        assert!(uses_index(&indexed, "//p", true));
+        assert!(uses_index(&indexed, "//p[@id=\"x\"]:nth=-1", true));
+        assert!(!uses_index(&plain, "//p", true));
+        // the index lists the whole document, not the part below a node
+        assert!(!uses_index(&indexed, "//p", false));
+        assert!(!uses_index(&indexed, "/p", true));
+
+        let section = indexed.root().children().nth(1).unwrap();
+        let xpath = XPathBuilder::parse("//p").unwrap();
+        let below = section
+            .query(&xpath)
+            .map(|node| node.text().trim().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(below, ["two", "three"]);
+    }
+}
@@ -1,7 +1,9 @@
 pub mod error;
 pub mod filter;
 pub mod parser;
+pub mod stream;
 pub mod xpath;
 
 pub use parser::XPathBuilder;
+pub use stream::XPathStream;
 pub use xpath::XPath;
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct XPathStep {
     pub axis: Axis,
     pub tag_name: String,
@@ -6,13 +6,13 @@ pub struct XPathStep {
     pub pos: Option<Position>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Axis {
     Child,
     Descendant,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SpType {
     Base,
     Contains,
@@ -20,7 +20,7 @@ pub enum SpType {
     Ends,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Condition {
     AttrExists(String),
     AttrCond {
@@ -32,15 +32,17 @@ pub enum Condition {
         sp_type: SpType,
         val: String,
     },
+    // a flag set with `Annotations::set_flag`
+    Flag(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LogicalOperator {
     And,
     Or,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Predicate {
     Expression(Condition),
     Logical {
@@ -50,7 +52,7 @@ pub enum Predicate {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Position {
     pub start: bool,
     pub pos: usize,
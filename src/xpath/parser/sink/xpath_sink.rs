@@ -42,6 +42,7 @@ impl XPathSink {
             Some(e) => Err(e),
             None => Ok(XPath {
                 steps: self.steps.take(),
+                normalization: None,
             }),
         }
     }
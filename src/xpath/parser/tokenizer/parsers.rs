@@ -2,8 +2,8 @@ use crate::{
     tup,
     utils::{
         parser::{
-            alpha1, alt, char, delimited, digit, error::PError, many0, map, opt, preceded, tag,
-            take_till, traits::Parser, trimmed, tuple,
+            alpha1, alt, char, delimited, digit, error::PError, many0, map, name1, opt, preceded,
+            tag, take_till, traits::Parser, trimmed, tuple,
         },
         ParseQueue,
     },
@@ -40,7 +40,7 @@ fn parse_axis() -> impl Parser<Output = Axis> {
 }
 
 fn parse_tag_name() -> impl Parser<Output = String> {
-    alpha1
+    name1
 }
 
 fn parse_predicates() -> impl Parser<Output = Vec<Predicate>> {
@@ -103,9 +103,10 @@ fn parse_logical_op() -> impl Parser<Output = LogicalOperator> {
 }
 
 fn parse_condition() -> impl Parser<Output = Predicate> {
-    map(alt(tup!(parse_attr(), parse_text())), |cond| {
-        Predicate::Expression(cond)
-    })
+    map(
+        alt(tup!(parse_attr(), parse_text(), parse_flag())),
+        Predicate::Expression,
+    )
 }
 
 fn parse_attr() -> impl Parser<Output = Condition> {
@@ -137,6 +138,10 @@ fn parse_text() -> impl Parser<Output = Condition> {
     }
 }
 
+fn parse_flag() -> impl Parser<Output = Condition> {
+    map(preceded(char('?'), name1), Condition::Flag)
+}
+
 fn parse_sp() -> impl Parser<Output = SpType> {
     |input: &mut ParseQueue| {
         Ok(alt(tup!(
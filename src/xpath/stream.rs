@@ -0,0 +1,408 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    dom::{
+        error::{DomError, DomResult},
+        parser::{
+            interface::{Tag, TagKind, Token, TokenSink, TokenSinkResult},
+            sink::DomSink,
+            tokenizer::Tokenizer,
+        },
+        Document,
+    },
+    utils::{CharQueue, Normalization, SharedStr},
+    xpath::{
+        error::{XPathError, XPathResult},
+        parser::interface::{Axis, Condition, Predicate, XPathStep},
+        XPath,
+    },
+};
+
+struct Frame {
+    name: SharedStr,
+    // reached[i] -> this element matched the first i steps
+    reached: Vec<bool>,
+    // descendant[i] -> an ancestor-or-self reached step i and step i is a descendant step
+    descendant: Vec<bool>,
+}
+
+/// Matches the downward-only subset of `XPath` against start and end tag
+/// events. Only the currently open elements are kept, so memory is bounded
+/// by the document depth.
+pub struct StreamMatcher {
+    steps: Vec<XPathStep>,
+    normalization: Option<Normalization>,
+    counters: Vec<usize>,
+    stack: Vec<Frame>,
+}
+
+impl StreamMatcher {
+    pub fn new(xpath: &XPath) -> XPathResult<Self> {
+        for step in xpath.steps.iter() {
+            if step.predicates.iter().any(needs_document) {
+                return Err(XPathError::Error {
+                    msg: format!(
+                        "Text and flag predicates on '{}' can't be streamed",
+                        step.tag_name
+                    ),
+                });
+            }
+            if step.pos.as_ref().is_some_and(|pos| !pos.start) {
+                return Err(XPathError::Error {
+                    msg: format!("Negative :nth on '{}' can't be streamed", step.tag_name),
+                });
+            }
+        }
+
+        Ok(StreamMatcher {
+            steps: xpath.steps.clone(),
+            normalization: xpath.normalization,
+            counters: vec![0; xpath.steps.len()],
+            stack: Vec::new(),
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Opens an element, returns whether it matches the whole expression.
+    pub fn start(&mut self, tag: &Tag) -> bool {
+        let len = self.steps.len();
+        let parent = self.stack.last();
+
+        // the top level element is the context node, as with `Document::query`
+        let mut reached = vec![false; len + 1];
+        reached[0] = parent.is_none();
+
+        let normalization = self.normalization;
+        for (i, step) in self.steps.iter().enumerate() {
+            let candidate = match step.axis {
+                Axis::Child => parent.is_some_and(|p| p.reached[i]),
+                Axis::Descendant => reached[i] || parent.is_some_and(|p| p.descendant[i]),
+            };
+
+            if candidate
+                && step.tag_name == tag.name.as_ref()
+                && step
+                    .predicates
+                    .iter()
+                    .all(|p| p.evaluate_attrs(tag.attributes(), normalization))
+            {
+                self.counters[i] += 1;
+                reached[i + 1] = step
+                    .pos
+                    .as_ref()
+                    .is_none_or(|pos| pos.pos == self.counters[i]);
+            }
+        }
+
+        let descendant = (0..=len)
+            .map(|i| {
+                i < len
+                    && matches!(self.steps[i].axis, Axis::Descendant)
+                    && (reached[i] || parent.is_some_and(|p| p.descendant[i]))
+            })
+            .collect();
+
+        let matched = reached[len];
+        self.stack.push(Frame {
+            name: tag.name.clone(),
+            reached,
+            descendant,
+        });
+        matched
+    }
+
+    /// Closes the innermost open element named `name` along with any unclosed
+    /// elements inside it. Returns false if no such element is open.
+    pub fn end(&mut self, name: &str) -> bool {
+        match self.stack.iter().rposition(|f| f.name.as_ref() == name) {
+            Some(idx) => {
+                self.stack.truncate(idx);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// text and flags are only known once the document is built
+fn needs_document(predicate: &Predicate) -> bool {
+    match predicate {
+        Predicate::Expression(cond) => {
+            matches!(cond, Condition::TextCond { .. } | Condition::Flag(_))
+        }
+        Predicate::Logical { left, right, .. } => needs_document(left) || needs_document(right),
+    }
+}
+
+struct Capture {
+    depth: usize,
+    sink: DomSink,
+}
+
+/// Sink driving a `StreamMatcher` that builds only the matched subtrees.
+/// A subtree that fails to build, e.g. on an invalid char, is reported as an
+/// error in place of its `Document`.
+pub struct StreamSink {
+    matcher: Mutex<StreamMatcher>,
+    captures: Mutex<Vec<Capture>>,
+    matches: Mutex<VecDeque<DomResult<Document>>>,
+}
+
+impl StreamSink {
+    pub fn new(matcher: StreamMatcher) -> Self {
+        StreamSink {
+            matcher: matcher.into(),
+            captures: Vec::new().into(),
+            matches: VecDeque::new().into(),
+        }
+    }
+
+    pub fn pop(&self) -> Option<DomResult<Document>> {
+        self.matches.lock().unwrap().pop_front()
+    }
+
+    // closes every element still open, completing all captures
+    pub fn end(&self) {
+        let names = self
+            .matcher
+            .lock()
+            .unwrap()
+            .stack
+            .iter()
+            .map(|f| f.name.clone())
+            .collect::<Vec<_>>();
+        for name in names.into_iter().rev() {
+            self.handle_end(Tag {
+                kind: TagKind::EndTag,
+                name,
+                attrs: Vec::new(),
+                values: String::new(),
+            });
+        }
+    }
+
+    fn forward(&self, token: &Token) {
+        for capture in self.captures.lock().unwrap().iter() {
+            capture.sink.process_token(token.clone());
+        }
+    }
+
+    fn handle_start(&self, tag: Tag) -> TokenSinkResult {
+        let (matched, depth) = {
+            let mut matcher = self.matcher.lock().unwrap();
+            (matcher.start(&tag), matcher.depth())
+        };
+        let name = tag.name.clone();
+        let token = Token::Tag(tag);
+        self.forward(&token);
+
+        if matched {
+            let sink = DomSink::new();
+            sink.process_token(token);
+            self.captures.lock().unwrap().push(Capture { depth, sink });
+        }
+
+        match DomSink::special_tags().contains(name.as_ref()) {
+            true => TokenSinkResult::Special(name),
+            false => TokenSinkResult::Continue,
+        }
+    }
+
+    fn handle_end(&self, tag: Tag) -> TokenSinkResult {
+        let depth = {
+            let mut matcher = self.matcher.lock().unwrap();
+            if !matcher.end(&tag.name) {
+                return TokenSinkResult::Continue;
+            }
+            matcher.depth()
+        };
+        self.forward(&Token::Tag(tag));
+
+        let mut captures = self.captures.lock().unwrap();
+        while captures.last().is_some_and(|c| c.depth > depth) {
+            let capture = captures.pop().unwrap();
+            self.matches.lock().unwrap().push_back(capture.sink.end());
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+impl TokenSink for StreamSink {
+    fn process_token(&self, token: Token) -> TokenSinkResult {
+        match token {
+            Token::Tag(tag) => match tag.kind {
+                TagKind::StartTag => self.handle_start(tag),
+                TagKind::EndTag => self.handle_end(tag),
+                TagKind::EmptyTag => {
+                    let name = tag.name.clone();
+                    self.handle_start(Tag {
+                        kind: TagKind::StartTag,
+                        ..tag
+                    });
+                    self.handle_end(Tag {
+                        kind: TagKind::EndTag,
+                        name,
+                        attrs: Vec::new(),
+                        values: String::new(),
+                    })
+                }
+            },
+            // an invalid char only fails the captures it's part of
+            Token::Text(_) | Token::InvalidChar(_) => {
+                self.forward(&token);
+                TokenSinkResult::Continue
+            }
+            Token::EndOfInput => TokenSinkResult::Suspend,
+            _ => TokenSinkResult::Continue,
+        }
+    }
+}
+
+/// Evaluates an `XPath` while tokenizing, without building the `Document`.
+/// Only the matching subtrees are materialized, each as a small standalone
+/// `Document`, and can be taken as soon as their end tag has been read.
+/// A match whose subtree fails to build is yielded as an error.
+pub struct XPathStream {
+    tokenizer: RefCell<Tokenizer<StreamSink>>,
+    sink: Arc<StreamSink>,
+}
+
+impl XPathStream {
+    /// Fails if any of the matches fails to build.
+    pub fn parse(xpath: &XPath, input: &str) -> XPathResult<Vec<Document>> {
+        let stream = Self::new(xpath)?;
+        stream.feed(input);
+        stream
+            .finalize()
+            .into_iter()
+            .collect::<DomResult<_>>()
+            .map_err(|err: DomError| XPathError::Error {
+                msg: err.to_string(),
+            })
+    }
+
+    pub fn new(xpath: &XPath) -> XPathResult<Self> {
+        let sink = Arc::new(StreamSink::new(StreamMatcher::new(xpath)?));
+        let tokenizer = RefCell::new(Tokenizer::new(sink.clone()));
+        Ok(Self { tokenizer, sink })
+    }
+
+    pub fn feed(&self, input: &str) {
+        self.tokenizer.borrow_mut().feed(CharQueue::from_str(input));
+    }
+
+    pub fn finalize(self) -> Vec<DomResult<Document>> {
+        self.tokenizer.borrow_mut().end();
+        self.sink.end();
+        std::iter::from_fn(|| self.sink.pop()).collect()
+    }
+}
+
+impl Iterator for XPathStream {
+    type Item = DomResult<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sink.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dom::{DomBuilder, DomNode},
+        xpath::XPathBuilder,
+    };
+
+    // tag and text of a node followed by its children
+    fn outline(node: DomNode) -> String {
+        let children = node.children().map(outline).collect::<String>();
+        format!("{}({}){}", node.tag(), node.text().trim(), children)
+    }
+
+    fn outlines(documents: &[Document]) -> Vec<String> {
+        documents.iter().map(|doc| outline(doc.root())).collect()
+    }
+
+    // what querying the whole document finds
+    fn queried(xpath: &XPath, input: &str) -> Vec<String> {
+        let document = DomBuilder::parse(input).unwrap();
+        document.query(xpath).map(outline).collect()
+    }
+
+    #[test]
+    fn captures_matching_subtrees() {
+        let xpath = XPathBuilder::parse("//li[@class=\"x\"]").unwrap();
+        let input = "<ul><li class=\"x\">a<b>b</b></li><li>c</li><li class=\"x\">d</li></ul>";
+        let nodes = XPathStream::parse(&xpath, input).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(outlines(&nodes), queried(&xpath, input));
+    }
+
+    #[test]
+    fn matches_are_available_after_their_end_tag() {
+        let xpath = XPathBuilder::parse("//p").unwrap();
+        let mut stream = XPathStream::new(&xpath).unwrap();
+        stream.feed("<div><p>one</p><p>tw");
+        let first = stream.next().unwrap().unwrap();
+        assert_eq!(first.root().text_content().as_str(), "one");
+        assert!(stream.next().is_none());
+
+        stream.feed("o</p></div>");
+        let rest = stream.finalize().into_iter().collect::<DomResult<Vec<_>>>();
+        assert_eq!(outlines(&rest.unwrap()), ["p(two)"]);
+    }
+
+    #[test]
+    fn nested_matches_are_captured_separately() {
+        let xpath = XPathBuilder::parse("//div").unwrap();
+        let nodes = XPathStream::parse(&xpath, "<div>a<div>b</div></div>").unwrap();
+        // inner matches complete first
+        assert_eq!(outlines(&nodes), ["div(b)", "div(a b)div(b)"]);
+    }
+
+    #[test]
+    fn unclosed_elements_are_closed_at_the_end() {
+        let xpath = XPathBuilder::parse("//p").unwrap();
+        let nodes = XPathStream::parse(&xpath, "<html><p>a</p><p>b").unwrap();
+        assert_eq!(outlines(&nodes), ["p(a)", "p(b)"]);
+    }
+
+    #[test]
+    fn failed_captures_are_reported() {
+        let xpath = XPathBuilder::parse("//p").unwrap();
+        let sink = StreamSink::new(StreamMatcher::new(&xpath).unwrap());
+        let tag = |kind, name: &str| {
+            Token::Tag(Tag {
+                kind,
+                name: Arc::from(name),
+                attrs: Vec::new(),
+                values: String::new(),
+            })
+        };
+        sink.process_token(tag(TagKind::StartTag, "div"));
+        sink.process_token(tag(TagKind::StartTag, "p"));
+        sink.process_token(Token::InvalidChar('\u{1}'));
+        sink.process_token(tag(TagKind::EndTag, "p"));
+        sink.process_token(tag(TagKind::StartTag, "p"));
+        sink.process_token(tag(TagKind::EndTag, "p"));
+        sink.end();
+
+        assert!(sink.pop().unwrap().is_err());
+        assert!(sink.pop().unwrap().is_ok());
+        assert!(sink.pop().is_none());
+    }
+
+    #[test]
+    fn text_predicates_are_rejected() {
+        let xpath = XPathBuilder::parse("//p[text=\"a\"]").unwrap();
+        assert!(XPathStream::new(&xpath).is_err());
+    }
+}
@@ -1,6 +1,19 @@
+use crate::utils::Normalization;
+
 use super::parser::interface::XPathStep;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct XPath {
     pub steps: Vec<XPathStep>,
+    // applied to both sides of text and attribute predicates
+    pub normalization: Option<Normalization>,
+}
+
+impl XPath {
+    /// Compares text and attribute values after normalizing them, e.g. case
+    /// insensitively.
+    pub fn normalized(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
 }